@@ -1,43 +1,28 @@
 class_name Ai
 
 
-static func pick_a_move(moves: Array[AnalyzedMove], difficulty: GameOptions.AIDifficulty) -> int:
-	var a = Analysis.new(moves)
+static var _players := {}
 
-	match difficulty:
-		GameOptions.AIDifficulty.Drunk:
-			if not a.winning_moves.is_empty() and randf() < 0.9:
-				return a.winning_moves.pick_random()
-			if not a.forced_moves.is_empty() and randf() < 0.9:
-				return a.forced_moves.pick_random()
-			return a.possible_moves.pick_random()
-		GameOptions.AIDifficulty.Normal:
-			if not a.winning_moves.is_empty():
-				return a.winning_moves.pick_random()
-			if not a.forced_moves.is_empty():
-				return a.forced_moves.pick_random()
-			if not a.good_moves.is_empty() and randf() < 0.5:
-				return a.good_moves.pick_random()
-			if not a.neutral_moves.is_empty() and randf() < 0.9:
-				return a.neutral_moves.pick_random()
-			if not a.non_losing_moves.is_empty() and randf() < 0.9:
-				return a.non_losing_moves.pick_random()
-			return a.possible_moves.pick_random()
-		GameOptions.AIDifficulty.Veteran:
-			if not a.winning_moves.is_empty():
-				return a.winning_moves.pick_random()
-			if not a.forced_moves.is_empty():
-				return a.forced_moves.pick_random()
-			if not a.okay_moves.is_empty():
-				return a.okay_moves.pick_random()
-			return a.possible_moves.pick_random()
-		GameOptions.AIDifficulty.Godlike:
-			if not a.winning_moves.is_empty():
-				return a.winning_moves.pick_random()
-			return a.best_moves.pick_random()
-		_:
-			assert(false, "unknown personality")
-	return -1
+
+static func pick_a_move(
+	position: PackedByteArray, moves: Array[AnalyzedMove], difficulty: GameOptions.AIDifficulty
+) -> int:
+	if not _players.has(difficulty):
+		var player := C4Player.new()
+		match difficulty:
+			GameOptions.AIDifficulty.Drunk:
+				player.set_difficulty(C4Player.EASY)
+			GameOptions.AIDifficulty.Normal:
+				player.set_difficulty(C4Player.MEDIUM)
+			GameOptions.AIDifficulty.Veteran:
+				player.set_difficulty(C4Player.HARD)
+			GameOptions.AIDifficulty.Godlike:
+				player.set_difficulty(C4Player.PERFECT)
+			_:
+				assert(false, "unknown personality")
+				return -1
+		_players[difficulty] = player
+	return _players[difficulty].choose_move(position, moves)
 
 
 class Analysis:
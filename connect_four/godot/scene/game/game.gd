@@ -252,5 +252,5 @@ func _on_solver_solved(pos: PackedByteArray, moves: Array[AnalyzedMove]) -> void
 	var pid = pos.size() % 2 + 1
 	if not PlayerManager.get_player_is_ai(pid):
 		return
-	var move = Ai.pick_a_move(moves, PlayerManager.get_player_ai_difficulty(pid))
+	var move = Ai.pick_a_move(pos, moves, PlayerManager.get_player_ai_difficulty(pid))
 	play_a_move(move)
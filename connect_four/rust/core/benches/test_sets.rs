@@ -0,0 +1,138 @@
+//! mean time and node count per position of the `Test_L*_R*` datasets, weakly and strongly,
+//! like the tables of http://blog.gamesolver.org/solving-connect-four/
+//!
+//! `cargo bench -p c4solver-core -- [dataset names, e.g. L2_R1]`, the slow L1_R2 and L1_R3
+//! sets only run when named.
+//!
+//! `BENCH_LINES` limits the number of positions of each dataset (default 100).
+//! the table is cleared before every position, so the results don't depend on their order.
+//!
+//! `BENCH_TABLES` lists the transposition tables to compare, `mru` (default) and `bucket`,
+//! e.g. `BENCH_TABLES=mru,bucket`. each takes `BENCH_TABLE_MB` megabytes (default 40).
+
+use std::time::Duration;
+
+use c4solver_core::lookup::TranspositionTable;
+
+type Position = c4solver_core::position::Position<7, 6>;
+type Solver<T> = c4solver_core::solver::Solver<7, 6, u64, T>;
+type MRUTable = c4solver_core::lookup::MRUTable<u64, u32, u8>;
+type BucketTable = c4solver_core::lookup::BucketTable<u64, u32>;
+
+/// name, data and whether the dataset runs without filters
+const DATASETS: [(&str, &str, bool); 6] = [
+    ("L3_R1", include_str!("../src/Test_L3_R1"), true),
+    ("L2_R1", include_str!("../src/Test_L2_R1"), true),
+    ("L2_R2", include_str!("../src/Test_L2_R2"), true),
+    ("L1_R1", include_str!("../src/Test_L1_R1"), true),
+    ("L1_R2", include_str!("../src/Test_L1_R2"), false),
+    ("L1_R3", include_str!("../src/Test_L1_R3"), false),
+];
+
+const DEFAULT_LINES: usize = 100;
+const DEFAULT_TABLE_MB: usize = 40;
+
+struct Summary {
+    positions: usize,
+    elapsed: Duration,
+    nodes: u64,
+}
+
+impl Summary {
+    fn mean_time(&self) -> Duration {
+        self.elapsed / self.positions.max(1) as u32
+    }
+    fn mean_nodes(&self) -> f64 {
+        self.nodes as f64 / self.positions.max(1) as f64
+    }
+    /// thousands of nodes per second
+    fn speed(&self) -> f64 {
+        self.nodes as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON) / 1000.0
+    }
+}
+
+fn run<T: TranspositionTable<u64>>(
+    solver: &mut Solver<T>,
+    data: &str,
+    lines: usize,
+    weak: bool,
+) -> Summary {
+    let mut summary = Summary {
+        positions: 0,
+        elapsed: Duration::ZERO,
+        nodes: 0,
+    };
+    for line in data.lines().take(lines) {
+        let (moves, expected) = line.split_once(' ').expect("moves and score");
+        let mut expected: i32 = expected.parse().expect("score");
+        if weak {
+            expected = expected.signum();
+        }
+        let mut position = Position::default();
+        position.apply_str(moves);
+        solver.clear_table();
+        let score = solver.solve(&position, weak);
+        assert_eq!(score, expected, "{moves}");
+        let stats = solver.last_stats();
+        summary.positions += 1;
+        summary.elapsed += stats.elapsed;
+        summary.nodes += stats.nodes;
+    }
+    summary
+}
+
+fn print_row(dataset: &str, table: &str, weak: bool, summary: &Summary) {
+    println!(
+        "{dataset:<8}{table:<8}{:<8}{:>10}{:>16}{:>16.1}{:>12.0}",
+        if weak { "weak" } else { "strong" },
+        summary.positions,
+        format!("{:?}", summary.mean_time()),
+        summary.mean_nodes(),
+        summary.speed(),
+    );
+}
+
+fn main() {
+    // `cargo bench` passes `--bench`
+    let filters: Vec<String> = std::env::args()
+        .skip(1)
+        .filter(|arg| !arg.starts_with('-'))
+        .collect();
+    let lines = std::env::var("BENCH_LINES").map_or(DEFAULT_LINES, |v| {
+        v.parse().expect("BENCH_LINES is a number of positions")
+    });
+    let table_mb = std::env::var("BENCH_TABLE_MB").map_or(DEFAULT_TABLE_MB, |v| {
+        v.parse().expect("BENCH_TABLE_MB is a number of megabytes")
+    });
+    let tables = std::env::var("BENCH_TABLES").unwrap_or("mru".to_string());
+    let tables: Vec<&str> = tables.split(',').collect();
+    if let Some(table) = tables.iter().find(|t| !["mru", "bucket"].contains(t)) {
+        panic!("unknown table {table} in BENCH_TABLES");
+    }
+    let solver = |name| tables.contains(&name).then_some(table_mb << 20);
+    let mut mru = solver("mru").map(Solver::<MRUTable>::with_table_bytes);
+    let mut bucket = solver("bucket").map(Solver::<BucketTable>::with_table_bytes);
+
+    println!(
+        "{:<8}{:<8}{:<8}{:>10}{:>16}{:>16}{:>12}",
+        "dataset", "table", "mode", "positions", "mean time", "mean nodes", "K nodes/s"
+    );
+    for (name, data, default) in DATASETS {
+        let selected = if filters.is_empty() {
+            default
+        } else {
+            filters.iter().any(|f| name.contains(f.as_str()))
+        };
+        if !selected {
+            continue;
+        }
+        for weak in [true, false] {
+            if let Some(solver) = &mut mru {
+                print_row(name, "mru", weak, &run(solver, data, lines, weak));
+            }
+            if let Some(solver) = &mut bucket {
+                print_row(name, "bucket", weak, &run(solver, data, lines, weak));
+            }
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use num_traits::PrimInt;
+use num_traits::sign::Unsigned;
+use std::ops::{BitAndAssign, BitOrAssign, BitXorAssign, ShlAssign};
+
+/// unsigned integer used as a bitboard by `Position`.
+///
+/// u64 covers boards up to 64 cells including the sentinel row (e.g. 7x6, 8x7),
+/// u128 covers larger ones (e.g. 8x8, 9x7).
+pub trait Bitboard:
+    PrimInt
+    + Unsigned
+    + BitAndAssign
+    + BitOrAssign
+    + BitXorAssign
+    + ShlAssign<usize>
+    + Default
+    + Send
+    + Sync
+    + 'static
+{
+    const BITS: usize;
+    /// partial key stored in the transposition table, see `MRUTable`
+    type PartialKey: PrimInt + Unsigned + Send + Sync + 'static;
+
+    /// truncating conversion, used to materialize masks computed as u128 constants
+    fn from_u128(v: u128) -> Self;
+}
+
+impl Bitboard for u64 {
+    const BITS: usize = 64;
+    type PartialKey = u32;
+
+    #[inline(always)]
+    fn from_u128(v: u128) -> Self {
+        v as u64
+    }
+}
+
+impl Bitboard for u128 {
+    const BITS: usize = 128;
+    type PartialKey = u64;
+
+    #[inline(always)]
+    fn from_u128(v: u128) -> Self {
+        v
+    }
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+
+/// errors from applying an invalid move sequence to a `Position`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum C4Error {
+    /// column (0-based) is outside the board
+    InvalidColumn(usize),
+    /// column (0-based) has no empty cell left
+    FullColumn(usize),
+    /// a move was played after a player already connected four
+    GameOver,
+    /// a move string contained something other than a 1-based column digit
+    BadCharacter(char),
+}
+
+impl fmt::Display for C4Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidColumn(col) => write!(f, "invalid column {col}"),
+            Self::FullColumn(col) => write!(f, "column {col} is full"),
+            Self::GameOver => write!(f, "game is already over"),
+            Self::BadCharacter(c) => write!(f, "bad character {c:?} in move string"),
+        }
+    }
+}
+
+impl std::error::Error for C4Error {}
+
+/// errors from loading an `OpeningBook`
+#[derive(Debug)]
+pub enum BookError {
+    Io(std::io::Error),
+    /// the header is not one of a book this version can read
+    BadHeader(&'static str),
+    /// the entries take a different number of bytes than the header says
+    Truncated {
+        expected: usize,
+        found: usize,
+    },
+    /// the entries don't match the checksum in the header
+    Checksum,
+    /// the entries don't describe a valid table
+    Corrupt(&'static str),
+    /// the book is for a `found` (width, height) board instead of `expected`
+    WrongSize {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read book: {e}"),
+            Self::BadHeader(reason) => write!(f, "bad book header: {reason}"),
+            Self::Truncated { expected, found } => {
+                write!(
+                    f,
+                    "expected {expected} bytes of book entries, found {found}"
+                )
+            }
+            Self::Checksum => write!(f, "book checksum mismatch"),
+            Self::Corrupt(reason) => write!(f, "corrupt book: {reason}"),
+            Self::WrongSize { expected, found } => write!(
+                f,
+                "book is for a {}x{} board, not {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BookError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for BookError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// errors from `Solver::load_table`
+#[derive(Debug)]
+pub enum TableError {
+    Io(std::io::Error),
+    /// the header is not one of a table this version can read
+    BadHeader(&'static str),
+    /// the table was saved for a `found` (width, height) board instead of `expected`
+    WrongSize {
+        expected: (usize, usize),
+        found: (usize, usize),
+    },
+    /// the table was saved with `found` entries of its type, the solver's table has `expected`
+    WrongTableSize {
+        expected: usize,
+        found: usize,
+    },
+    /// the entries take a different number of bytes than the header says
+    Truncated {
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl fmt::Display for TableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "cannot read table: {e}"),
+            Self::BadHeader(reason) => write!(f, "bad table header: {reason}"),
+            Self::WrongSize { expected, found } => write!(
+                f,
+                "table is for a {}x{} board, not {}x{}",
+                found.0, found.1, expected.0, expected.1
+            ),
+            Self::WrongTableSize { expected, found } => {
+                write!(f, "table has {found} entries instead of {expected}")
+            }
+            Self::Truncated { expected, found } => {
+                write!(
+                    f,
+                    "expected {expected} bytes of table entries, found {found}"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for TableError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for TableError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
@@ -0,0 +1,110 @@
+use crate::bitboard::Bitboard;
+use crate::error::C4Error;
+use crate::position::Position;
+
+/// a `Position` together with its move history, supporting undo and redo
+#[derive(Clone)]
+pub struct Game<const W: usize = 7, const H: usize = 6, B: Bitboard = u64> {
+    position: Position<W, H, B>,
+    /// positions before each played move
+    history: Vec<Position<W, H, B>>,
+    moves: Vec<usize>,
+    /// undone moves, most recently undone last
+    undone: Vec<usize>,
+}
+impl<const W: usize, const H: usize, B: Bitboard> Default for Game<W, H, B> {
+    fn default() -> Self {
+        Self {
+            position: Position::default(),
+            history: vec![],
+            moves: vec![],
+            undone: vec![],
+        }
+    }
+}
+impl<const W: usize, const H: usize, B: Bitboard> Game<W, H, B> {
+    pub fn position(&self) -> &Position<W, H, B> {
+        &self.position
+    }
+    pub fn moves(&self) -> &[usize] {
+        &self.moves
+    }
+
+    pub fn can_play(&self, col: usize) -> bool {
+        col < W && !self.position.is_game_over() && self.position.can_play(col)
+    }
+
+    /// plays `col` and forgets any undone moves
+    pub fn play(&mut self, col: usize) -> Result<(), C4Error> {
+        self.push(col)?;
+        self.undone.clear();
+        Ok(())
+    }
+    fn push(&mut self, col: usize) -> Result<(), C4Error> {
+        if self.position.winner().is_some() {
+            return Err(C4Error::GameOver);
+        }
+        let mut new = self.position;
+        new.try_apply_moves([col])?;
+        self.history.push(self.position);
+        self.moves.push(col);
+        self.position = new;
+        Ok(())
+    }
+
+    /// takes back the last move, returning its column
+    pub fn undo(&mut self) -> Option<usize> {
+        let col = self.moves.pop()?;
+        // history and moves always have the same length
+        self.position = self.history.pop()?;
+        self.undone.push(col);
+        Some(col)
+    }
+    /// replays the last undone move, returning its column
+    pub fn redo(&mut self) -> Option<usize> {
+        let col = self.undone.pop()?;
+        self.push(col).ok()?;
+        Some(col)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn undo_redo() {
+        let mut game = Game::<7, 6>::default();
+        for col in [3, 3, 2] {
+            game.play(col).unwrap();
+        }
+        assert_eq!(game.undo(), Some(2));
+        assert_eq!(game.undo(), Some(3));
+        assert_eq!(game.moves(), &[3]);
+        assert_eq!(game.position().n_moves(), 1);
+        assert_eq!(game.redo(), Some(3));
+        assert_eq!(game.moves(), &[3, 3]);
+
+        // playing a new move forgets the undone ones
+        game.play(4).unwrap();
+        assert_eq!(game.redo(), None);
+        assert_eq!(game.moves(), &[3, 3, 4]);
+
+        while game.undo().is_some() {}
+        assert_eq!(game.position().n_moves(), 0);
+        assert_eq!(game.redo(), Some(3));
+    }
+
+    #[test]
+    fn play_until_won() {
+        let mut game = Game::<7, 6>::default();
+        for col in [0, 1, 0, 1, 0, 1, 0] {
+            assert!(game.can_play(col));
+            game.play(col).unwrap();
+        }
+        assert_eq!(game.position().winner(), Some(0));
+        assert!(!game.can_play(1));
+        assert_eq!(game.play(1), Err(C4Error::GameOver));
+        assert_eq!(game.play(9), Err(C4Error::GameOver));
+    }
+}
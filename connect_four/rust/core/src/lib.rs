@@ -0,0 +1,11 @@
+//! the Connect Four engine, without the Godot classes of the `c4solver` crate
+
+pub mod bitboard;
+pub mod error;
+pub mod game;
+pub mod lookup;
+pub mod outcome;
+pub mod player;
+pub mod position;
+pub mod puzzle;
+pub mod solver;
@@ -0,0 +1,964 @@
+use crate::bitboard::Bitboard;
+use crate::error::BookError;
+use crate::position::Position;
+use num_traits::PrimInt;
+use num_traits::sign::Unsigned;
+use std::marker::PhantomData;
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// storage of the bounds found by `Solver`, keyed by `Position::key`
+pub trait TranspositionTable<K>: Default {
+    /// identifies the type of table in saved files
+    const FORMAT: u8;
+    /// bytes taken by each entry in `save`
+    const ENTRY_BYTES: usize;
+
+    fn get(&self, key: K) -> Option<u8>;
+    /// returns true if an entry of another key was replaced
+    fn put(&mut self, key: K, value: u8) -> bool;
+    /// `put` for a position with `_depth` moves left to play,
+    /// tables may keep entries that took more work to find longer
+    fn put_with_depth(&mut self, key: K, value: u8, _depth: usize) -> bool {
+        self.put(key, value)
+    }
+    /// called when a search starts, tables may prefer keeping the entries of the current one
+    fn new_search(&mut self) {}
+
+    /// number of entries
+    fn size(&self) -> usize;
+    /// appends every entry to `bytes`, see `Solver::save_table`
+    fn save(&self, bytes: &mut Vec<u8>);
+    /// replaces every entry with `bytes` written by `save` for a table of the same size
+    fn load(&mut self, bytes: &[u8]);
+
+    /// a table taking at most about `bytes` of memory, or more if it needs more entries to
+    /// tell keys of `key_bits` bits apart
+    fn with_bytes(bytes: usize, key_bits: usize) -> Self;
+    /// forgets every entry
+    fn clear(&mut self);
+}
+
+/// the low bits of `key` fitting in `PK`, `size_of::<K>()` must be at most 16
+fn partial_key<K: PrimInt + Unsigned, PK: PrimInt + Unsigned>(key: K) -> PK {
+    // safety: callers assert size_of::<K>() <= 16
+    let key = unsafe { key.to_u128().unwrap_unchecked() };
+    match size_of::<PK>() {
+        1 => unsafe { PK::from(key as u8).unwrap_unchecked() },
+        2 => unsafe { PK::from(key as u16).unwrap_unchecked() },
+        4 => unsafe { PK::from(key as u32).unwrap_unchecked() },
+        8 => unsafe { PK::from(key as u64).unwrap_unchecked() },
+        _ => unimplemented!(),
+    }
+}
+
+fn pk_to_le_bytes<PK: PrimInt + Unsigned>(key: PK) -> [u8; 16] {
+    // safety: partial keys are at most 16 bytes
+    unsafe { key.to_u128().unwrap_unchecked() }.to_le_bytes()
+}
+
+fn pk_from_le_bytes<PK: PrimInt + Unsigned>(bytes: &[u8]) -> PK {
+    let mut le = [0; 16];
+    le[..bytes.len()].copy_from_slice(bytes);
+    // safety: the bytes came from a PK
+    unsafe { PK::from(u128::from_le_bytes(le)).unwrap_unchecked() }
+}
+
+/// a prime number of entries, the largest fitting in `entries` but at least
+/// `1 << (key_bits - stored_bits)`: by the chinese remainder theorem, the index `key % size`
+/// and the low `stored_bits` of the key then tell every key of `key_bits` bits apart
+fn table_size(entries: usize, key_bits: usize, stored_bits: usize) -> usize {
+    let min = 1usize
+        .checked_shl(key_bits.saturating_sub(stored_bits) as u32)
+        .expect("the stored keys are too short");
+    prev_prime(entries).max(next_prime(min))
+}
+
+/// `key % len` for u64 or u128 keys
+fn key_index<K: PrimInt + Unsigned>(key: K, len: usize) -> usize {
+    // size_of::<K>() is known at compile time, 64-bit keys avoid the slow u128 division
+    if size_of::<K>() <= 8 {
+        // safety: checked size_of::<K>() <= 8
+        let key = unsafe { key.to_u64().unwrap_unchecked() };
+        (key % len as u64) as usize
+    } else {
+        // safety: callers assert size_of::<K>() <= 16
+        let key = unsafe { key.to_u128().unwrap_unchecked() };
+        (key % len as u128) as usize
+    }
+}
+
+/// identity hashed MRU table, keys are u64 or u128
+pub struct MRUTable<K, PK, V> {
+    keys: Vec<PK>,
+    values: Vec<V>,
+    _phantom: PhantomData<K>,
+}
+
+impl<K: PrimInt + Unsigned, PK: PrimInt + Unsigned, V: PrimInt + Unsigned> MRUTable<K, PK, V> {
+    pub fn new(log_size: usize) -> Self {
+        Self::with_size(next_prime(1 << log_size))
+    }
+    /// the largest prime number of entries fitting in `bytes`, see `table_size`
+    pub fn with_bytes(bytes: usize, key_bits: usize) -> Self {
+        let entries = bytes / (size_of::<PK>() + size_of::<V>());
+        Self::with_size(table_size(entries, key_bits, 8 * size_of::<PK>()))
+    }
+    fn with_size(size: usize) -> Self {
+        assert!(size_of::<K>() <= 16);
+        assert!(size_of::<PK>() <= size_of::<K>());
+        Self {
+            keys: vec![PK::zero(); size],
+            values: vec![V::zero(); size],
+            _phantom: Default::default(),
+        }
+    }
+
+    fn k_to_pk(key: K) -> PK {
+        partial_key(key)
+    }
+    fn index(&self, key: K) -> usize {
+        key_index(key, self.keys.len())
+    }
+
+    pub fn size(&self) -> usize {
+        self.keys.len()
+    }
+    pub fn clear(&mut self) {
+        self.keys.fill(PK::zero());
+        self.values.fill(V::zero());
+    }
+
+    /// returns true if an entry of another key was replaced
+    pub fn put(&mut self, key: K, value: V) -> bool {
+        let idx = self.index(key);
+        let pk = Self::k_to_pk(key);
+        // an empty slot has a zero key, real zero partial keys are rare enough to miscount
+        let overwritten = self.keys[idx] != pk && self.keys[idx] != PK::zero();
+        self.keys[idx] = pk;
+        self.values[idx] = value;
+        overwritten
+    }
+    pub fn get(&self, key: K) -> Option<V> {
+        let idx = self.index(key);
+        if self.keys[idx] == Self::k_to_pk(key) {
+            Some(self.values[idx])
+        } else {
+            None
+        }
+    }
+}
+
+impl<K: PrimInt + Unsigned, PK: PrimInt + Unsigned, V: PrimInt + Unsigned> Default
+    for MRUTable<K, PK, V>
+{
+    fn default() -> Self {
+        Self::new(23)
+    }
+}
+
+impl<K: PrimInt + Unsigned, PK: PrimInt + Unsigned> TranspositionTable<K> for MRUTable<K, PK, u8> {
+    const FORMAT: u8 = 1;
+    const ENTRY_BYTES: usize = size_of::<PK>() + 1;
+
+    fn get(&self, key: K) -> Option<u8> {
+        MRUTable::get(self, key)
+    }
+    fn put(&mut self, key: K, value: u8) -> bool {
+        MRUTable::put(self, key, value)
+    }
+
+    fn size(&self) -> usize {
+        MRUTable::size(self)
+    }
+    /// every key (little endian) then every value
+    fn save(&self, bytes: &mut Vec<u8>) {
+        for key in &self.keys {
+            bytes.extend(&pk_to_le_bytes(*key)[..size_of::<PK>()]);
+        }
+        bytes.extend(&self.values);
+    }
+    fn load(&mut self, bytes: &[u8]) {
+        let (keys, values) = bytes.split_at(self.keys.len() * size_of::<PK>());
+        for (key, chunk) in self.keys.iter_mut().zip(keys.chunks_exact(size_of::<PK>())) {
+            *key = pk_from_le_bytes(chunk);
+        }
+        self.values.copy_from_slice(values);
+    }
+
+    fn with_bytes(bytes: usize, key_bits: usize) -> Self {
+        MRUTable::with_bytes(bytes, key_bits)
+    }
+    fn clear(&mut self) {
+        MRUTable::clear(self)
+    }
+}
+
+/// lock-free MRU table shared between threads through `Arc`, see `Solver::analyze_parallel`.
+///
+/// each entry is one word holding a 55-bit partial key, an occupied flag and the value,
+/// so concurrent readers never see a key paired with another key's value.
+pub struct SharedTable<K> {
+    entries: Vec<AtomicU64>,
+    _phantom: PhantomData<K>,
+}
+
+impl<K: PrimInt + Unsigned> SharedTable<K> {
+    const OCCUPIED: u64 = 1 << 8;
+    const KEY_BITS: usize = 55;
+
+    pub fn new(log_size: usize) -> Self {
+        Self::with_size(next_prime(1 << log_size))
+    }
+    /// the largest prime number of entries fitting in `bytes`, see `table_size`
+    pub fn with_bytes(bytes: usize, key_bits: usize) -> Self {
+        let entries = bytes / size_of::<AtomicU64>();
+        Self::with_size(table_size(entries, key_bits, Self::KEY_BITS))
+    }
+    fn with_size(size: usize) -> Self {
+        assert!(size_of::<K>() <= 16);
+        Self {
+            entries: (0..size).map(|_| AtomicU64::new(0)).collect(),
+            _phantom: Default::default(),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn clear(&self) {
+        for entry in &self.entries {
+            entry.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn tag(key: K) -> u64 {
+        // safety: new() asserted: size_of::<K>() <= 16
+        let key = unsafe { key.to_u128().unwrap_unchecked() };
+        ((key as u64) << (64 - Self::KEY_BITS)) | Self::OCCUPIED
+    }
+
+    pub fn get(&self, key: K) -> Option<u8> {
+        let entry = self.entries[key_index(key, self.entries.len())].load(Ordering::Relaxed);
+        (entry & !0xff == Self::tag(key)).then_some(entry as u8)
+    }
+    /// returns true if an entry of another key was replaced
+    pub fn put(&self, key: K, value: u8) -> bool {
+        let tag = Self::tag(key);
+        let old = self.entries[key_index(key, self.entries.len())]
+            .swap(tag | value as u64, Ordering::Relaxed);
+        old & Self::OCCUPIED != 0 && old & !0xff != tag
+    }
+}
+
+impl<K: PrimInt + Unsigned> Default for SharedTable<K> {
+    fn default() -> Self {
+        Self::new(23)
+    }
+}
+
+impl<K: PrimInt + Unsigned> TranspositionTable<K> for Arc<SharedTable<K>> {
+    const FORMAT: u8 = 2;
+    const ENTRY_BYTES: usize = size_of::<u64>();
+
+    fn get(&self, key: K) -> Option<u8> {
+        SharedTable::get(self, key)
+    }
+    fn put(&mut self, key: K, value: u8) -> bool {
+        SharedTable::put(self, key, value)
+    }
+
+    fn size(&self) -> usize {
+        SharedTable::size(self)
+    }
+    /// every entry, little endian
+    fn save(&self, bytes: &mut Vec<u8>) {
+        for entry in &self.entries {
+            bytes.extend(entry.load(Ordering::Relaxed).to_le_bytes());
+        }
+    }
+    fn load(&mut self, bytes: &[u8]) {
+        for (entry, chunk) in self.entries.iter().zip(bytes.chunks_exact(8)) {
+            entry.store(
+                u64::from_le_bytes(chunk.try_into().unwrap()),
+                Ordering::Relaxed,
+            );
+        }
+    }
+
+    fn with_bytes(bytes: usize, key_bits: usize) -> Self {
+        Arc::new(SharedTable::with_bytes(bytes, key_bits))
+    }
+    fn clear(&mut self) {
+        SharedTable::clear(self)
+    }
+}
+
+/// table of buckets of `BUCKET_SIZE` entries. when a bucket is full, entries of previous
+/// searches are replaced first, then the ones with the fewest moves left.
+/// like `MRUTable`, buckets are indexed by `key % size` and keep partial keys.
+pub struct BucketTable<K, PK> {
+    buckets: Vec<Bucket<PK>>,
+    /// increased by every search, wrapping at `AGE_MASK`
+    age: u8,
+    _phantom: PhantomData<K>,
+}
+
+const BUCKET_SIZE: usize = 4;
+
+#[derive(Clone, Copy)]
+struct Bucket<PK> {
+    keys: [PK; BUCKET_SIZE],
+    values: [u8; BUCKET_SIZE],
+    /// age << 6 | (moves left + 1), 0 for empty entries
+    meta: [u8; BUCKET_SIZE],
+}
+
+impl<K: PrimInt + Unsigned, PK: PrimInt + Unsigned> BucketTable<K, PK> {
+    const AGE_SHIFT: u32 = 6;
+    const DEPTH_MASK: u8 = (1 << Self::AGE_SHIFT) - 1;
+
+    /// about `1 << log_size` entries
+    pub fn new(log_size: usize) -> Self {
+        Self::with_buckets(next_prime((1 << log_size) / BUCKET_SIZE))
+    }
+    /// the largest prime number of buckets fitting in `bytes`, see `table_size`
+    pub fn with_bytes(bytes: usize, key_bits: usize) -> Self {
+        let buckets = bytes / size_of::<Bucket<PK>>();
+        Self::with_buckets(table_size(buckets, key_bits, 8 * size_of::<PK>()))
+    }
+    fn with_buckets(buckets: usize) -> Self {
+        assert!(size_of::<K>() <= 16);
+        assert!(size_of::<PK>() <= size_of::<K>());
+        let empty = Bucket {
+            keys: [PK::zero(); BUCKET_SIZE],
+            values: [0; BUCKET_SIZE],
+            meta: [0; BUCKET_SIZE],
+        };
+        Self {
+            buckets: vec![empty; buckets],
+            age: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// number of entries
+    pub fn size(&self) -> usize {
+        self.buckets.len() * BUCKET_SIZE
+    }
+    pub fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            bucket.meta = [0; BUCKET_SIZE];
+        }
+    }
+
+    fn find(bucket: &Bucket<PK>, pk: PK) -> Option<usize> {
+        (0..BUCKET_SIZE).find(|&i| bucket.keys[i] == pk && bucket.meta[i] != 0)
+    }
+
+    pub fn get(&self, key: K) -> Option<u8> {
+        let bucket = &self.buckets[key_index(key, self.buckets.len())];
+        Self::find(bucket, partial_key(key)).map(|i| bucket.values[i])
+    }
+    /// `depth` is the number of moves left to play, returns true if an entry of another key
+    /// was replaced
+    pub fn put(&mut self, key: K, value: u8, depth: usize) -> bool {
+        let pk = partial_key(key);
+        let age = self.age;
+        let idx = key_index(key, self.buckets.len());
+        let bucket = &mut self.buckets[idx];
+        let meta = age << Self::AGE_SHIFT | (depth + 1).min(Self::DEPTH_MASK as usize) as u8;
+        let (i, overwritten) = match Self::find(bucket, pk) {
+            Some(i) => (i, false),
+            None => {
+                let i = (0..BUCKET_SIZE)
+                    .min_by_key(|&i| {
+                        let meta = bucket.meta[i];
+                        let depth = meta & Self::DEPTH_MASK;
+                        (depth != 0, meta >> Self::AGE_SHIFT == age, depth)
+                    })
+                    .expect("buckets are not empty");
+                (i, bucket.meta[i] != 0)
+            }
+        };
+        bucket.keys[i] = pk;
+        bucket.values[i] = value;
+        bucket.meta[i] = meta;
+        overwritten
+    }
+
+    fn new_search(&mut self) {
+        self.age = (self.age + 1) & (u8::MAX >> Self::AGE_SHIFT);
+    }
+}
+
+impl<K: PrimInt + Unsigned, PK: PrimInt + Unsigned> Default for BucketTable<K, PK> {
+    fn default() -> Self {
+        Self::new(23)
+    }
+}
+
+impl<K: PrimInt + Unsigned, PK: PrimInt + Unsigned> TranspositionTable<K> for BucketTable<K, PK> {
+    const FORMAT: u8 = 3;
+    const ENTRY_BYTES: usize = size_of::<PK>() + 2;
+
+    fn get(&self, key: K) -> Option<u8> {
+        BucketTable::get(self, key)
+    }
+    fn put(&mut self, key: K, value: u8) -> bool {
+        BucketTable::put(self, key, value, 0)
+    }
+    fn put_with_depth(&mut self, key: K, value: u8, depth: usize) -> bool {
+        BucketTable::put(self, key, value, depth)
+    }
+    fn new_search(&mut self) {
+        BucketTable::new_search(self)
+    }
+
+    fn size(&self) -> usize {
+        BucketTable::size(self)
+    }
+    /// the keys (little endian), values and metadata of each bucket
+    fn save(&self, bytes: &mut Vec<u8>) {
+        for bucket in &self.buckets {
+            for key in bucket.keys {
+                bytes.extend(&pk_to_le_bytes(key)[..size_of::<PK>()]);
+            }
+            bytes.extend(bucket.values);
+            bytes.extend(bucket.meta);
+        }
+    }
+    fn load(&mut self, bytes: &[u8]) {
+        let chunks = bytes.chunks_exact(BUCKET_SIZE * Self::ENTRY_BYTES);
+        for (bucket, chunk) in self.buckets.iter_mut().zip(chunks) {
+            let (keys, rest) = chunk.split_at(BUCKET_SIZE * size_of::<PK>());
+            for (key, k) in bucket
+                .keys
+                .iter_mut()
+                .zip(keys.chunks_exact(size_of::<PK>()))
+            {
+                *key = pk_from_le_bytes(k);
+            }
+            let (values, meta) = rest.split_at(BUCKET_SIZE);
+            bucket.values.copy_from_slice(values);
+            bucket.meta.copy_from_slice(meta);
+        }
+    }
+
+    fn with_bytes(bytes: usize, key_bits: usize) -> Self {
+        BucketTable::with_bytes(bytes, key_bits)
+    }
+    fn clear(&mut self) {
+        BucketTable::clear(self)
+    }
+}
+
+const fn has_factor(n: usize, min: usize, max: usize) -> bool {
+    if (min as u64 * min as u64) > n as u64 {
+        return false;
+    }
+    if min + 1 >= max {
+        return n.is_multiple_of(min);
+    }
+    has_factor(n, min, (min + max) / 2) || has_factor(n, (min + max) / 2, max)
+}
+
+const fn next_prime(n: usize) -> usize {
+    if has_factor(n, 2, n) {
+        next_prime(n + 1)
+    } else {
+        n
+    }
+}
+
+/// the largest prime at most `n`, 2 if there is none
+const fn prev_prime(n: usize) -> usize {
+    if n <= 2 {
+        2
+    } else if has_factor(n, 2, n) {
+        prev_prime(n - 1)
+    } else {
+        n
+    }
+}
+
+/// scores of the positions up to some depth, precomputed by solving them
+pub struct OpeningBook {
+    table: MRUTable<u64, u16, u8>,
+    width: usize,
+    height: usize,
+    depth: usize,
+    /// the table has `next_prime(1 << log_size)` entries
+    log_size: usize,
+}
+impl OpeningBook {
+    const MAGIC: &[u8; 4] = b"C4BK";
+    const VERSION: u8 = 1;
+    /// see `to_bytes`
+    const HEADER_LEN: usize = 17;
+    /// see `from_legacy_bytes`
+    const LEGACY_HEADER_LEN: usize = 6;
+    /// `next_prime` is slow to evaluate beyond this
+    const MAX_LOG_SIZE: usize = 32;
+    /// `Position::key3` doesn't fit deeper positions, whose key has `depth + width` digits
+    const MAX_DEPTH_PLUS_WIDTH: usize = 40;
+
+    /// an empty book of `depth` for a `width`x`height` board with a table of about
+    /// `1 << log_size` entries, see `Solver::generate_opening_book`
+    pub fn new(width: usize, height: usize, depth: usize, log_size: usize) -> Self {
+        assert!(log_size <= Self::MAX_LOG_SIZE);
+        assert!(
+            depth + width <= Self::MAX_DEPTH_PLUS_WIDTH,
+            "Position::key3 doesn't fit deeper positions"
+        );
+        Self {
+            table: MRUTable::new(log_size),
+            width,
+            height,
+            depth,
+            log_size,
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+    /// (width, height) of the board
+    pub fn size(&self) -> (usize, usize) {
+        (self.width, self.height)
+    }
+    /// added to scores to store them, so that 0 is never a valid entry: one more than minus the
+    /// lowest score of the board, like Pascal Pons' books (19 on 7x6)
+    fn value_offset(&self) -> i32 {
+        (self.width * self.height / 2) as i32 - 2
+    }
+
+    /// returns None for positions deeper than the book or of a different board size
+    pub fn get<const W: usize, const H: usize, B: Bitboard>(
+        &self,
+        position: &Position<W, H, B>,
+    ) -> Option<i32> {
+        if W != self.width || H != self.height || position.n_moves() > self.depth {
+            None
+        } else {
+            self.table
+                .get(position.key3())
+                // an empty slot whose zero key happens to match
+                .filter(|&v| v != 0)
+                .map(|v| v as i32 - self.value_offset())
+        }
+    }
+    /// stores the exact `score` of `position`, which must fit in the book
+    pub fn insert<const W: usize, const H: usize, B: Bitboard>(
+        &mut self,
+        position: &Position<W, H, B>,
+        score: i32,
+    ) {
+        assert_eq!((W, H), self.size());
+        assert!(position.n_moves() <= self.depth);
+        let value = score + self.value_offset();
+        assert!(
+            (1..=u8::MAX as i32).contains(&value),
+            "score {score} out of range"
+        );
+        self.table.put(position.key3(), value as u8);
+    }
+
+    pub fn load_from_path(path: impl AsRef<Path>) -> Result<Self, BookError> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    pub fn save_to_path(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.to_bytes())
+    }
+
+    /// the `.book` file read by `from_bytes`, in the current format: a header of
+    /// - `MAGIC`, `VERSION`, width, height, depth and log2 of the table size, one byte each
+    /// - the number of entries and the CRC-32 of the entries, u32 little endian each
+    ///
+    /// followed by the occupied slots of the table by increasing index, each one as the
+    /// LEB128 difference to the index of the previous one, the u16 key and the u8 value
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut entries = vec![];
+        let mut count = 0u32;
+        let mut last = 0;
+        for (idx, (key, value)) in self.table.keys.iter().zip(&self.table.values).enumerate() {
+            if *key == 0 && *value == 0 {
+                continue;
+            }
+            write_leb128(&mut entries, idx - last);
+            entries.extend(key.to_le_bytes());
+            entries.push(*value);
+            last = idx;
+            count += 1;
+        }
+        let mut bytes = Vec::with_capacity(Self::HEADER_LEN + entries.len());
+        bytes.extend(Self::MAGIC);
+        bytes.extend(
+            [
+                Self::VERSION as usize,
+                self.width,
+                self.height,
+                self.depth,
+                self.log_size,
+            ]
+            .map(|b| b as u8),
+        );
+        bytes.extend(count.to_le_bytes());
+        bytes.extend(crc32(&entries).to_le_bytes());
+        bytes.extend(entries);
+        bytes
+    }
+
+    /// parses a `.book` file written by `to_bytes`, or by Pascal Pons' solver
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        match bytes.strip_prefix(Self::MAGIC) {
+            Some(bytes) => Self::from_current_bytes(bytes),
+            None => Self::from_legacy_bytes(bytes),
+        }
+    }
+
+    fn from_current_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        let Some((header, data)) = bytes.split_at_checked(Self::HEADER_LEN - Self::MAGIC.len())
+        else {
+            return Err(BookError::BadHeader("too short"));
+        };
+        let [version, w, h, depth, log_size] = std::array::from_fn(|i| header[i] as usize);
+        if version != Self::VERSION as usize {
+            return Err(BookError::BadHeader("unsupported version"));
+        }
+        let count = u32::from_le_bytes(header[5..9].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[9..13].try_into().unwrap());
+        // each entry takes at least 4 bytes
+        if data.len() < 4 * count {
+            return Err(BookError::Truncated {
+                expected: 4 * count,
+                found: data.len(),
+            });
+        }
+        if crc32(data) != crc {
+            return Err(BookError::Checksum);
+        }
+        let mut book = Self::empty(w, h, depth, log_size)?;
+        let mut rest = data;
+        let mut idx = 0;
+        for _ in 0..count {
+            let delta;
+            (delta, rest) = read_leb128(rest).ok_or(BookError::Corrupt("entries end early"))?;
+            idx += delta;
+            let (&[k0, k1, value], tail) = rest
+                .split_first_chunk()
+                .ok_or(BookError::Corrupt("entries end early"))?;
+            rest = tail;
+            if idx >= book.table.size() {
+                return Err(BookError::Corrupt("entry outside of the table"));
+            }
+            book.table.keys[idx] = u16::from_le_bytes([k0, k1]);
+            book.table.values[idx] = value;
+        }
+        if !rest.is_empty() {
+            return Err(BookError::Corrupt("bytes after the last entry"));
+        }
+        Ok(book)
+    }
+
+    /// the header is width, height, depth, key bytes, value bytes and log2 of the table size,
+    /// followed by every key of the table (little endian) then every value
+    fn from_legacy_bytes(bytes: &[u8]) -> Result<Self, BookError> {
+        let Some((header, data)) = bytes.split_at_checked(Self::LEGACY_HEADER_LEN) else {
+            return Err(BookError::BadHeader("too short"));
+        };
+        let [w, h, depth, pk_size, v_size, log_size] = std::array::from_fn(|i| header[i] as usize);
+        if pk_size != size_of::<u16>() || v_size != size_of::<u8>() {
+            return Err(BookError::BadHeader("unsupported key or value size"));
+        }
+        let mut book = Self::empty(w, h, depth, log_size)?;
+        let size = book.table.size();
+        let expected = size * (pk_size + v_size);
+        if data.len() != expected {
+            return Err(BookError::Truncated {
+                expected,
+                found: data.len(),
+            });
+        }
+        let (keys_bytes, value_bytes) = data.split_at(pk_size * size);
+        book.table.keys = keys_bytes
+            .chunks_exact(pk_size)
+            .map(|k| u16::from_le_bytes([k[0], k[1]]))
+            .collect();
+        book.table.values = value_bytes.to_vec();
+        Ok(book)
+    }
+
+    /// `new` for a header read from a file
+    fn empty(
+        width: usize,
+        height: usize,
+        depth: usize,
+        log_size: usize,
+    ) -> Result<Self, BookError> {
+        if width == 0 || height == 0 {
+            return Err(BookError::BadHeader("empty board"));
+        }
+        if log_size > Self::MAX_LOG_SIZE {
+            return Err(BookError::BadHeader("table too large"));
+        }
+        if depth + width > Self::MAX_DEPTH_PLUS_WIDTH {
+            return Err(BookError::BadHeader("too deep"));
+        }
+        Ok(Self {
+            table: MRUTable::new(log_size),
+            width,
+            height,
+            depth,
+            log_size,
+        })
+    }
+}
+
+fn write_leb128(bytes: &mut Vec<u8>, mut v: usize) {
+    while v >= 0x80 {
+        bytes.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    bytes.push(v as u8);
+}
+
+/// returns the value and the bytes after it
+fn read_leb128(bytes: &[u8]) -> Option<(usize, &[u8])> {
+    let mut v = 0;
+    for (i, b) in bytes
+        .iter()
+        .enumerate()
+        .take(usize::BITS.div_ceil(7) as usize)
+    {
+        v |= ((b & 0x7f) as usize) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((v, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+/// CRC-32 (IEEE 802.3) of `bytes`
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// a legacy book of `log_size` holding the given positions
+    fn book_bytes(log_size: u8, depth: u8, entries: &[(&str, i32)]) -> Vec<u8> {
+        let size = next_prime(1 << log_size);
+        let mut keys = vec![0u16; size];
+        let mut values = vec![0u8; size];
+        for (moves, score) in entries {
+            let mut p = Position::<7, 6>::default();
+            p.apply_str(moves);
+            let idx = key_index(p.key3(), size);
+            keys[idx] = p.key3() as u16;
+            values[idx] = (score + 19) as u8;
+        }
+        let mut bytes = vec![7, 6, depth, 2, 1, log_size];
+        bytes.extend(keys.iter().flat_map(|k| k.to_le_bytes()));
+        bytes.extend(values);
+        bytes
+    }
+
+    #[test]
+    fn from_bytes() {
+        let bytes = book_bytes(10, 4, &[("4444", 1), ("352", -2)]);
+        let book = OpeningBook::from_bytes(&bytes).unwrap();
+        assert_eq!(book.size(), (7, 6));
+        assert_eq!(book.depth(), 4);
+        let get = |moves: &str| {
+            let mut p = Position::<7, 6>::default();
+            p.apply_str(moves);
+            book.get(&p)
+        };
+        assert_eq!(get("4444"), Some(1));
+        assert_eq!(get("352"), Some(-2));
+        assert_eq!(get("1"), None);
+        assert!(book.get(&Position::<6, 5>::default()).is_none());
+
+        assert!(matches!(
+            OpeningBook::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(BookError::Truncated { .. })
+        ));
+        assert!(matches!(
+            OpeningBook::from_bytes(&bytes[..3]),
+            Err(BookError::BadHeader(_))
+        ));
+        let mut wide_keys = bytes.clone();
+        wide_keys[3] = 4;
+        assert!(matches!(
+            OpeningBook::from_bytes(&wide_keys),
+            Err(BookError::BadHeader(_))
+        ));
+        // Position::key3 would overflow on the deepest positions
+        assert!(matches!(
+            OpeningBook::from_bytes(&book_bytes(10, 34, &[])),
+            Err(BookError::BadHeader(_))
+        ));
+        assert!(OpeningBook::from_bytes(&book_bytes(10, 33, &[])).is_ok());
+    }
+
+    #[test]
+    fn current_format() {
+        let mut book = OpeningBook::new(7, 6, 4, 16);
+        let entries = [("", 1), ("4", -1), ("44", 0), ("3521", -18), ("7777", 18)];
+        for (moves, score) in entries {
+            let mut p = Position::<7, 6>::default();
+            p.apply_str(moves);
+            book.insert(&p, score);
+        }
+        let bytes = book.to_bytes();
+        assert!(bytes.len() < OpeningBook::HEADER_LEN + 4 * entries.len() + 16);
+        let loaded = OpeningBook::from_bytes(&bytes).unwrap();
+        assert_eq!((loaded.size(), loaded.depth()), ((7, 6), 4));
+        assert_eq!(loaded.to_bytes(), bytes);
+        for (moves, score) in entries {
+            let mut p = Position::<7, 6>::default();
+            p.apply_str(moves);
+            assert_eq!(loaded.get(&p), Some(score), "{moves}");
+        }
+
+        let mut corrupt = bytes.clone();
+        *corrupt.last_mut().unwrap() ^= 1;
+        assert!(matches!(
+            OpeningBook::from_bytes(&corrupt),
+            Err(BookError::Checksum)
+        ));
+        let mut newer = bytes.clone();
+        newer[4] += 1;
+        assert!(matches!(
+            OpeningBook::from_bytes(&newer),
+            Err(BookError::BadHeader(_))
+        ));
+        assert!(matches!(
+            OpeningBook::from_bytes(&bytes[..bytes.len() - 5]),
+            Err(BookError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn large_board() {
+        // the lowest and highest scores of 9x7, and -19 that the 7x6 offset stored as 0
+        let mut book = OpeningBook::new(9, 7, 2, 12);
+        let entries = [("", -28), ("5", -19), ("19", 29), ("99", 0)];
+        for (moves, score) in entries {
+            let mut p = Position::<9, 7, u128>::default();
+            p.apply_str(moves);
+            book.insert(&p, score);
+        }
+        let loaded = OpeningBook::from_bytes(&book.to_bytes()).unwrap();
+        assert_eq!((loaded.size(), loaded.depth()), ((9, 7), 2));
+        for (moves, score) in entries {
+            let mut p = Position::<9, 7, u128>::default();
+            p.apply_str(moves);
+            assert_eq!(loaded.get(&p), Some(score), "{moves}");
+        }
+        let mut p = Position::<9, 7, u128>::default();
+        p.apply_str("12");
+        assert_eq!(loaded.get(&p), None);
+    }
+
+    #[test]
+    fn with_bytes() {
+        assert_eq!(prev_prime(200), 199);
+        assert_eq!(prev_prime(0), 2);
+        let mut table = MRUTable::<u64, u32, u8>::with_bytes(1000, 32);
+        assert_eq!(table.size(), 199);
+        table.put(42, 1);
+        assert_eq!(table.get(42), Some(1));
+        table.clear();
+        assert_eq!(table.get(42), None);
+        let table = SharedTable::<u64>::with_bytes(1000, 49);
+        assert_eq!(table.size(), 113);
+        table.put(42, 1);
+        table.clear();
+        assert_eq!(table.get(42), None);
+
+        // too small for the partial keys to tell 49-bit keys apart
+        let table = MRUTable::<u64, u32, u8>::with_bytes(1000, 49);
+        assert_eq!(table.size(), next_prime(1 << 17));
+        let table = SharedTable::<u128>::with_bytes(1000, 72);
+        assert_eq!(table.size(), next_prime(1 << 17));
+        let table = BucketTable::<u64, u32>::with_bytes(1000, 49);
+        assert_eq!(table.size(), next_prime(1 << 17) * BUCKET_SIZE);
+    }
+
+    #[test]
+    fn bucket_table() {
+        type Table = BucketTable<u64, u32>;
+        let mut table = Table::with_bytes(2 * size_of::<Bucket<u32>>(), 32);
+        assert_eq!(table.size(), 2 * BUCKET_SIZE);
+        assert!(!table.put(0, 0, 0));
+        assert_eq!(table.get(0), Some(0));
+        assert_eq!(table.get(2), None);
+        // the 4 even keys share a bucket, the deepest entries are kept
+        for key in 1..4 {
+            assert!(!table.put(2 * key, 10 + key as u8, 10 + key as usize));
+        }
+        assert!(table.put(8, 1, 20));
+        assert_eq!(table.get(0), None);
+        assert!(table.put(10, 1, 0));
+        assert_eq!(table.get(10), Some(1));
+        assert_eq!(table.get(2), None);
+        assert_eq!(table.get(6), Some(13));
+        // entries of previous searches go first
+        TranspositionTable::new_search(&mut table);
+        assert!(table.put(12, 1, 0));
+        assert_eq!(table.get(10), None);
+        assert!(table.put(14, 1, 0));
+        assert_eq!(table.get(4), None);
+        assert_eq!(table.get(8), Some(1));
+
+        let mut bytes = vec![];
+        table.save(&mut bytes);
+        assert_eq!(bytes.len(), table.size() * Table::ENTRY_BYTES);
+        let mut loaded = Table::with_bytes(2 * size_of::<Bucket<u32>>(), 32);
+        loaded.load(&bytes);
+        for key in [6, 8, 12, 14] {
+            assert_eq!(loaded.get(key), table.get(key));
+        }
+    }
+
+    #[test]
+    fn leb128() {
+        for v in [0, 1, 127, 128, 300, 1 << 20, usize::MAX] {
+            let mut bytes = vec![];
+            write_leb128(&mut bytes, v);
+            bytes.push(42);
+            assert_eq!(read_leb128(&bytes), Some((v, &[42][..])));
+        }
+        assert_eq!(read_leb128(&[0x80]), None);
+    }
+
+    #[test]
+    #[ignore = "needs the 7x6_small.book built by Pascal Pons' solver in src/"]
+    fn load_opening_book() {
+        let book =
+            OpeningBook::load_from_path(concat!(env!("CARGO_MANIFEST_DIR"), "/src/7x6_small.book"))
+                .unwrap();
+        let test_case = |code: &str| {
+            let mut p = Position::<7, 6>::default();
+            p.apply_str(code);
+            eprintln!("{code} {}", book.get(&p).unwrap());
+        };
+        test_case("352");
+        test_case("32453");
+        test_case("11122");
+    }
+}
@@ -0,0 +1,273 @@
+//! command line engine for 7x6 boards, reading one line at a time from stdin.
+//!
+//! a line of moves like the `Test_L*` files (e.g. `4453`) prints the moves and their score,
+//! in the same format.
+//! otherwise the line is one of the commands:
+//! - `position [moves]`: set the position to play from, empty for the start
+//! - `analyze [weak]`: print `scores` and one score per column, `-` for full columns
+//! - `go [weak]`: print `score` and the score of the position
+//! - `bestmove [weak]`: print `bestmove` and the column (1-based) of a best move
+//! - `quit`
+//!
+//! scores are from the point of view of the player to move.
+//! errors are printed as `error <reason>`, with 1-based columns, and leave the position untouched.
+
+use std::io::BufRead;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use c4solver_core::error::C4Error;
+use c4solver_core::lookup::OpeningBook;
+
+type Position = c4solver_core::position::Position<7, 6>;
+type Solver = c4solver_core::solver::Solver<7, 6>;
+
+const USAGE: &str = "usage: c4solver-cli [-w] [-b <opening book>] [-t <table MB>]
+  -w  solve lines of moves weakly (win, draw or loss) instead of strongly";
+
+struct Options {
+    weak: bool,
+    book: Option<String>,
+    table_mb: Option<usize>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        weak: false,
+        book: None,
+        table_mb: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-w" => options.weak = true,
+            "-b" => options.book = Some(args.next().ok_or("-b needs a path")?),
+            "-t" => {
+                let mb = args.next().ok_or("-t needs a size")?;
+                options.table_mb = Some(mb.parse().map_err(|_| format!("bad table size {mb}"))?);
+            }
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    Ok(options)
+}
+
+struct Engine {
+    solver: Solver,
+    position: Position,
+    /// set by `quit`
+    quit: bool,
+}
+
+impl Engine {
+    /// the reply to `line`, if any
+    fn run(&mut self, line: &str, weak: bool) -> Result<Option<String>, C4Error> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(None);
+        };
+        let weak_arg = words.next() == Some("weak");
+        let position = self.position;
+        match command {
+            "quit" => {
+                self.quit = true;
+                Ok(None)
+            }
+            "position" => {
+                let mut position = Position::default();
+                if let Some(moves) = line.split_whitespace().nth(1) {
+                    position.try_apply_str(moves)?;
+                }
+                self.position = position;
+                Ok(None)
+            }
+            "analyze" => {
+                let scores = self.scores(&position, weak_arg)?;
+                let scores: Vec<_> = scores
+                    .iter()
+                    .map(|s| s.map_or("-".to_string(), |s| s.to_string()))
+                    .collect();
+                Ok(Some(format!("scores {}", scores.join(" "))))
+            }
+            "go" => {
+                let score = self.solve(&position, weak_arg)?;
+                Ok(Some(format!("score {score}")))
+            }
+            "bestmove" => {
+                let scores = self.scores(&position, weak_arg)?;
+                // the first of the best columns from the center outwards
+                let best = Position::MOVE_ORDER
+                    .into_iter()
+                    .filter_map(|col| Some((col, scores[col]?)))
+                    .rev()
+                    .max_by_key(|&(_, score)| score);
+                Ok(Some(match best {
+                    Some((col, _)) => format!("bestmove {}", col + 1),
+                    None => "bestmove none".to_string(),
+                }))
+            }
+            _ => {
+                // anything after the moves, like the expected score in the test files, is ignored
+                let mut position = Position::default();
+                position.try_apply_str(command)?;
+                let score = self.solve(&position, weak)?;
+                Ok(Some(format!("{command} {score}")))
+            }
+        }
+    }
+
+    fn solve(&mut self, position: &Position, weak: bool) -> Result<i32, C4Error> {
+        position.check_not_over()?;
+        Ok(self.solver.solve(position, weak))
+    }
+
+    /// scores of every column from the point of view of the player to move
+    fn scores(
+        &mut self,
+        position: &Position,
+        weak: bool,
+    ) -> Result<[Option<i32>; Position::WIDTH], C4Error> {
+        position.check_not_over()?;
+        let scores = self.solver.analyze(position, weak);
+        Ok(scores.map(|s| s.map(|s| -s)))
+    }
+}
+
+/// `e` with the 1-based columns of move strings
+fn error_message(e: C4Error) -> String {
+    match e {
+        C4Error::InvalidColumn(col) => C4Error::InvalidColumn(col + 1).to_string(),
+        C4Error::FullColumn(col) => C4Error::FullColumn(col + 1).to_string(),
+        e => e.to_string(),
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut solver = match options.table_mb {
+        Some(mb) => Solver::with_table_bytes(mb << 20),
+        None => Solver::default(),
+    };
+    if let Some(path) = &options.book {
+        let book = OpeningBook::load_from_path(path).map(Arc::new);
+        if let Err(e) = book.and_then(|book| solver.set_opening_book(Some(book))) {
+            eprintln!("cannot use opening book {path}: {e}");
+            return ExitCode::FAILURE;
+        }
+    }
+    let mut engine = Engine {
+        solver,
+        position: Position::default(),
+        quit: false,
+    };
+
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        match engine.run(&line, options.weak) {
+            Ok(Some(reply)) => println!("{reply}"),
+            Ok(None) => {}
+            Err(e) => println!("error {}", error_message(e)),
+        }
+        if engine.quit {
+            break;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn engine() -> Engine {
+        Engine {
+            solver: Solver::with_table_bytes(1 << 20),
+            position: Position::default(),
+            quit: false,
+        }
+    }
+
+    fn reply(engine: &mut Engine, line: &str) -> Option<String> {
+        engine.run(line, false).unwrap()
+    }
+
+    #[test]
+    fn moves() {
+        let mut engine = engine();
+        for line in include_str!("Test_L2_R1").lines().take(20) {
+            // the expected score is ignored, and printed back by the solver
+            assert_eq!(reply(&mut engine, line), Some(line.to_string()));
+            let (moves, score) = line.split_once(' ').unwrap();
+            let score = score.parse::<i32>().unwrap().signum();
+            assert_eq!(
+                engine.run(moves, true),
+                Ok(Some(format!("{moves} {score}")))
+            );
+        }
+        assert_eq!(reply(&mut engine, ""), None);
+    }
+
+    #[test]
+    fn commands() {
+        let mut engine = engine();
+        assert_eq!(reply(&mut engine, "position 52753311433677442422121"), None);
+        assert_eq!(reply(&mut engine, "go").unwrap(), "score 8");
+        assert_eq!(reply(&mut engine, "go weak").unwrap(), "score 1");
+        assert_eq!(
+            reply(&mut engine, "analyze").unwrap(),
+            "scores 2 3 7 7 8 7 2"
+        );
+        assert_eq!(reply(&mut engine, "bestmove").unwrap(), "bestmove 5");
+
+        reply(&mut engine, "position 5554224333234511764415115");
+        assert_eq!(
+            reply(&mut engine, "analyze").unwrap(),
+            "scores -8 -8 -8 -8 - 4 -8"
+        );
+        // columns 1, 3, 5, 6 and 7 are as good, the closest to the center wins
+        reply(&mut engine, "position 715371563635542612576371");
+        assert_eq!(
+            reply(&mut engine, "analyze").unwrap(),
+            "scores 3 2 3 -9 3 3 3"
+        );
+        assert_eq!(reply(&mut engine, "bestmove").unwrap(), "bestmove 3");
+
+        assert_eq!(reply(&mut engine, "position"), None);
+        assert_eq!(engine.position.n_moves(), 0);
+        assert!(!engine.quit);
+        assert_eq!(reply(&mut engine, "quit"), None);
+        assert!(engine.quit);
+    }
+
+    #[test]
+    fn errors() {
+        let mut engine = engine();
+        reply(&mut engine, "position 4453");
+        assert_eq!(
+            engine.run("position 9", false),
+            Err(C4Error::InvalidColumn(8))
+        );
+        assert_eq!(
+            engine.run("position 4x", false),
+            Err(C4Error::BadCharacter('x'))
+        );
+        assert_eq!(engine.run("4444444", false), Err(C4Error::FullColumn(3)));
+        // the position is left untouched
+        assert_eq!(engine.position.n_moves(), 4);
+        assert_eq!(error_message(C4Error::InvalidColumn(8)), "invalid column 9");
+        assert_eq!(error_message(C4Error::FullColumn(3)), "column 4 is full");
+
+        reply(&mut engine, "position 1212121");
+        for line in ["go", "analyze", "bestmove", "1212121"] {
+            assert_eq!(engine.run(line, false), Err(C4Error::GameOver));
+        }
+    }
+}
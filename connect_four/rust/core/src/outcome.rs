@@ -0,0 +1,241 @@
+use std::fmt;
+
+use crate::bitboard::Bitboard;
+use crate::position::Position;
+
+/// result of a game for one player
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameResult {
+    Win,
+    Draw,
+    Loss,
+}
+
+/// how a game ends if both sides play perfectly, see `Outcome::new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Outcome {
+    /// for the player to move
+    pub result: GameResult,
+    /// plies until the game ends, the last move included
+    pub plies: usize,
+    /// 0 for the first player, 1 for the second, None for a draw
+    pub winner: Option<usize>,
+}
+
+impl Outcome {
+    /// the outcome of `position` with `score` (see `Solver::solve`) for the player to move.
+    /// scores beyond a win or loss with the next move are clamped to it.
+    pub fn new<const W: usize, const H: usize, B: Bitboard>(
+        position: &Position<W, H, B>,
+        score: i32,
+    ) -> Self {
+        let mover = position.current_player();
+        let remaining = position.remaining_moves() as i32;
+        let score = score.clamp(-remaining / 2, (remaining + 1) / 2);
+        if score == 0 {
+            return Self {
+                result: GameResult::Draw,
+                plies: position.remaining_moves(),
+                winner: None,
+            };
+        }
+        // the winning move is played on a board of n stones and scores (W * H + 1 - n) / 2,
+        // n has the parity of the winner's turns
+        let t = 2 * score.unsigned_abs() as usize;
+        let parity = (position.n_moves() + (score < 0) as usize) % 2;
+        let n = [(W * H + 1).saturating_sub(t), (W * H).saturating_sub(t)]
+            .into_iter()
+            .find(|n| n % 2 == parity)
+            .expect("one of two consecutive numbers has the parity");
+        let plies = n.saturating_sub(position.n_moves()) + 1;
+        if score > 0 {
+            Self {
+                result: GameResult::Win,
+                plies,
+                winner: Some(mover),
+            }
+        } else {
+            Self {
+                result: GameResult::Loss,
+                plies,
+                winner: Some(1 - mover),
+            }
+        }
+    }
+}
+
+/// why a move is good or bad in a few words, see `MoveReason::new`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveReason {
+    WinsNow,
+    /// the opponent can win with the next move
+    LetsOpponentWin,
+    /// the opponent would win here with the next move
+    BlocksThreat,
+    /// every other move lets the opponent win
+    OnlyNonLosing,
+    /// the opponent can't stop every threat
+    DoubleThreat,
+    /// within this many moves of the player, if the score tells
+    Wins(Option<usize>),
+    KeepsDraw,
+    /// within this many moves of the player, if the score tells
+    Loses(Option<usize>),
+}
+
+impl MoveReason {
+    /// the main reason to play (or not) `col` in `position`, where it scores `score` (see
+    /// `Solver::solve`) for the player to move. weak scores don't tell how far the end is.
+    pub fn new<const W: usize, const H: usize, B: Bitboard>(
+        position: &Position<W, H, B>,
+        col: usize,
+        score: i32,
+        weak: bool,
+    ) -> Self {
+        let move_bit = position.possible_moves() & Position::<W, H, B>::column_mask(col);
+        let child = position.played(col);
+        if position.is_winning_move(col) {
+            return Self::WinsNow;
+        }
+        if child.can_win_next() {
+            return Self::LetsOpponentWin;
+        }
+        if position.is_forced_move(col) {
+            return Self::BlocksThreat;
+        }
+        if position.possible_non_losing_moves() == move_bit {
+            return Self::OnlyNonLosing;
+        }
+        if position.score_move(move_bit) >= 2 && child.possible_non_losing_moves() == B::zero() {
+            return Self::DoubleThreat;
+        }
+        let outcome = Outcome::new(position, score);
+        let moves = (!weak).then(|| outcome.plies.div_ceil(2));
+        match outcome.result {
+            GameResult::Win => Self::Wins(moves),
+            GameResult::Draw => Self::KeepsDraw,
+            GameResult::Loss => Self::Loses(moves),
+        }
+    }
+}
+
+impl fmt::Display for MoveReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WinsNow => write!(f, "wins immediately"),
+            Self::LetsOpponentWin => write!(f, "lets the opponent win"),
+            Self::BlocksThreat => write!(f, "blocks a threat"),
+            Self::OnlyNonLosing => write!(f, "only non-losing move"),
+            Self::DoubleThreat => write!(f, "creates a double threat"),
+            Self::Wins(None) => write!(f, "wins"),
+            Self::Wins(Some(1)) => write!(f, "wins in 1 move"),
+            Self::Wins(Some(n)) => write!(f, "wins in {n} moves"),
+            Self::KeepsDraw => write!(f, "keeps the draw"),
+            Self::Loses(None) => write!(f, "loses"),
+            Self::Loses(Some(1)) => write!(f, "loses in 1 move"),
+            Self::Loses(Some(n)) => write!(f, "loses in {n} moves"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Position = crate::position::Position<7, 6>;
+    type Solver = crate::solver::Solver<7, 6>;
+
+    #[test]
+    fn plies_match_principal_variation() {
+        let mut solver = Solver::default();
+        for moves in [
+            "32164625",
+            "243335424257",
+            "5455174361263362",
+            "2737772244262123677516643354",
+        ] {
+            let mut position = Position::default();
+            position.apply_str(moves);
+            let score = solver.solve(&position, false);
+            let line = solver.principal_variation(&position);
+            let outcome = Outcome::new(&position, score);
+            assert_eq!(outcome.plies, line.len(), "{moves}");
+
+            let mut end = position;
+            end.apply_moves(line);
+            assert_eq!(outcome.winner, end.winner(), "{moves}");
+        }
+    }
+
+    #[test]
+    fn reason() {
+        // the score only matters once the position itself tells nothing
+        let reason = |moves: &str, col| {
+            let mut position = Position::default();
+            position.apply_str(moves);
+            MoveReason::new(&position, col, 0, false)
+        };
+        // the first player can win in column 1, or else the second player wins in column 2
+        assert_eq!(reason("121212", 0), MoveReason::WinsNow);
+        assert_eq!(reason("121212", 2), MoveReason::LetsOpponentWin);
+        // the second player must block column 1
+        assert_eq!(reason("1213126", 0), MoveReason::BlocksThreat);
+        // the first player gets both ends of the bottom row
+        assert_eq!(reason("4455", 2), MoveReason::DoubleThreat);
+        assert_eq!(reason("4455", 5), MoveReason::DoubleThreat);
+        assert_eq!(reason("4455", 0), MoveReason::KeepsDraw);
+
+        let mut solver = Solver::default();
+        let mut position = Position::default();
+        position.apply_str("6561461362133747245312317267");
+        let scores = solver.analyze(&position, false);
+        let reasons: Vec<_> = (0..7)
+            .filter_map(|col| Some(MoveReason::new(&position, col, -scores[col]?, false)))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                MoveReason::KeepsDraw,
+                MoveReason::KeepsDraw,
+                MoveReason::Loses(Some(2)),
+                MoveReason::KeepsDraw,
+                MoveReason::LetsOpponentWin,
+                MoveReason::Loses(Some(2)),
+                MoveReason::Loses(Some(3)),
+            ]
+        );
+        assert_eq!(
+            MoveReason::new(&position, 0, 3, false),
+            MoveReason::Wins(Some(5))
+        );
+        assert_eq!(
+            MoveReason::new(&position, 0, 3, true),
+            MoveReason::Wins(None)
+        );
+
+        assert_eq!(MoveReason::Wins(Some(3)).to_string(), "wins in 3 moves");
+        assert_eq!(MoveReason::Loses(None).to_string(), "loses");
+    }
+
+    #[test]
+    fn result() {
+        let mut position = Position::default();
+        position.apply_str("121212");
+        let win = Outcome::new(&position, 18);
+        assert_eq!(win.result, GameResult::Win);
+        assert_eq!((win.plies, win.winner), (1, Some(0)));
+        // the second player to move, losing with the next move of the first player
+        position.apply_str("3");
+        let loss = Outcome::new(&position, -17);
+        assert_eq!(loss.result, GameResult::Loss);
+        assert_eq!((loss.plies, loss.winner), (2, Some(0)));
+        let draw = Outcome::new(&Position::default(), 0);
+        assert_eq!(draw.result, GameResult::Draw);
+        assert_eq!((draw.plies, draw.winner), (42, None));
+
+        // impossible scores are the fastest possible results
+        assert_eq!(Outcome::new(&position, 100), Outcome::new(&position, 18));
+        assert_eq!(Outcome::new(&position, -100).plies, 2);
+        assert_eq!(Outcome::new(&Position::default(), i32::MIN).plies, 2);
+    }
+}
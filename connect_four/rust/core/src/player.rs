@@ -0,0 +1,251 @@
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::bitboard::Bitboard;
+use crate::outcome::{GameResult, Outcome};
+use crate::position::Position;
+
+/// how well a `Player` plays, from `EASY` to `PERFECT`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Strength {
+    /// softmax temperature over the scores, 0 always picks one of the best moves
+    pub temperature: f64,
+    /// chance to play a uniformly random move instead
+    pub blunder_chance: f64,
+    /// wins and losses more than this many plies away are seen as draws.
+    /// needs strong scores, weak ones don't tell how far away the end is.
+    pub max_depth: Option<usize>,
+    /// always win immediately if possible and block the opponent's immediate wins,
+    /// even when blundering
+    pub never_miss_tactics: bool,
+}
+impl Strength {
+    pub const EASY: Self = Self {
+        temperature: 3.0,
+        blunder_chance: 0.25,
+        max_depth: Some(2),
+        never_miss_tactics: false,
+    };
+    pub const MEDIUM: Self = Self {
+        temperature: 1.0,
+        blunder_chance: 0.1,
+        max_depth: Some(6),
+        never_miss_tactics: true,
+    };
+    pub const HARD: Self = Self {
+        temperature: 0.3,
+        blunder_chance: 0.02,
+        max_depth: Some(12),
+        never_miss_tactics: true,
+    };
+    pub const PERFECT: Self = Self {
+        temperature: 0.0,
+        blunder_chance: 0.0,
+        max_depth: None,
+        never_miss_tactics: true,
+    };
+}
+impl Default for Strength {
+    fn default() -> Self {
+        Self::PERFECT
+    }
+}
+
+/// picks moves from the scores of `Solver::analyze`, playing as well as its `Strength` allows
+pub struct Player {
+    pub strength: Strength,
+    rng: StdRng,
+}
+impl Default for Player {
+    fn default() -> Self {
+        Self::new(Strength::default())
+    }
+}
+impl Player {
+    pub fn new(strength: Strength) -> Self {
+        Self {
+            strength,
+            rng: StdRng::from_rng(&mut rand::rng()),
+        }
+    }
+    /// the same seed, strength and positions give the same moves
+    pub fn with_seed(strength: Strength, seed: u64) -> Self {
+        Self {
+            strength,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// `scores` has one entry per column, from the point of view of the player to move
+    /// (the opposite of `Solver::analyze`), None for full columns.
+    /// returns None if no column can be played.
+    pub fn choose<const W: usize, const H: usize, B: Bitboard>(
+        &mut self,
+        position: &Position<W, H, B>,
+        scores: &[Option<i32>],
+    ) -> Option<usize> {
+        let mut candidates: Vec<(usize, i32)> = (0..W)
+            .filter(|&col| position.can_play(col))
+            .filter_map(|col| {
+                let score = if position.is_winning_move(col) {
+                    (position.remaining_moves() + 1) as i32 / 2
+                } else {
+                    (*scores.get(col)?)?
+                };
+                Some((col, score))
+            })
+            .collect();
+        if self.strength.never_miss_tactics {
+            if let Some(&(col, _)) = candidates
+                .iter()
+                .find(|(col, _)| position.is_winning_move(*col))
+            {
+                return Some(col);
+            }
+            if candidates
+                .iter()
+                .any(|(col, _)| position.is_forced_move(*col))
+            {
+                candidates.retain(|(col, _)| position.is_forced_move(*col));
+            }
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+        if self
+            .rng
+            .random_bool(self.strength.blunder_chance.clamp(0.0, 1.0))
+        {
+            return candidates.choose(&mut self.rng).map(|(col, _)| *col);
+        }
+
+        let seen: Vec<(usize, f64)> = candidates
+            .iter()
+            .map(|&(col, score)| (col, self.seen_score(position, score) as f64))
+            .collect();
+        let best = seen.iter().map(|(_, s)| *s).fold(f64::MIN, f64::max);
+        let temperature = self.strength.temperature;
+        seen.choose_weighted(&mut self.rng, |&(_, s)| {
+            if temperature > 0.0 {
+                ((s - best) / temperature).exp()
+            } else if s == best {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .ok()
+        .map(|(col, _)| *col)
+    }
+
+    /// `score` as far as `Strength::max_depth` lets the player see
+    fn seen_score<const W: usize, const H: usize, B: Bitboard>(
+        &self,
+        position: &Position<W, H, B>,
+        score: i32,
+    ) -> i32 {
+        let outcome = Outcome::new(position, score);
+        match self.strength.max_depth {
+            Some(depth) if outcome.result != GameResult::Draw && outcome.plies > depth => 0,
+            _ => score,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Position = crate::position::Position<7, 6>;
+    type Solver = crate::solver::Solver<7, 6>;
+
+    /// scores from the point of view of the player to move
+    fn scores(solver: &mut Solver, position: &Position) -> Vec<Option<i32>> {
+        solver
+            .analyze(position, false)
+            .iter()
+            .map(|s| s.map(|s| -s))
+            .collect()
+    }
+
+    #[test]
+    fn perfect_plays_best() {
+        let mut solver = Solver::default();
+        let mut position = Position::default();
+        position.apply_str("5554224333234511764415115");
+        let scores = scores(&mut solver, &position);
+        let best = scores.iter().flatten().max().copied();
+        let mut player = Player::with_seed(Strength::PERFECT, 0);
+        for _ in 0..20 {
+            let col = player.choose(&position, &scores).unwrap();
+            assert_eq!(scores[col], best);
+        }
+    }
+
+    #[test]
+    fn never_miss_tactics() {
+        let strength = Strength {
+            blunder_chance: 1.0,
+            ..Strength::EASY
+        };
+        let mut player = Player::with_seed(
+            Strength {
+                never_miss_tactics: true,
+                ..strength
+            },
+            0,
+        );
+        let flat = [Some(0); 7];
+        let mut position = Position::default();
+        // the first player wins in column 1
+        position.apply_str("121312");
+        for _ in 0..20 {
+            assert_eq!(player.choose(&position, &flat), Some(0));
+        }
+        // the second player must block column 1
+        position.apply_str("6");
+        for _ in 0..20 {
+            assert_eq!(player.choose(&position, &flat), Some(0));
+        }
+
+        let mut player = Player::with_seed(strength, 0);
+        let cols: Vec<_> = (0..50)
+            .map(|_| player.choose(&position, &flat).unwrap())
+            .collect();
+        assert!(cols.iter().any(|&col| col != 0));
+    }
+
+    #[test]
+    fn max_depth_hides_distant_results() {
+        let strength = Strength {
+            max_depth: Some(4),
+            ..Strength::PERFECT
+        };
+        let player = Player::with_seed(strength, 0);
+        let position = Position::default();
+        assert_eq!(player.seen_score(&position, 1), 0);
+        assert_eq!(player.seen_score(&position, -18), 0);
+        let mut position = Position::default();
+        position.apply_str("121312");
+        assert_eq!(player.seen_score(&position, 18), 18);
+    }
+
+    #[test]
+    fn seeded() {
+        let mut solver = Solver::default();
+        let mut position = Position::default();
+        position.apply_str("5554224333234511764415115");
+        let scores = scores(&mut solver, &position);
+        let play = |seed| {
+            let mut player = Player::with_seed(Strength::EASY, seed);
+            (0..20)
+                .map(|_| player.choose(&position, &scores).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(play(7), play(7));
+    }
+}
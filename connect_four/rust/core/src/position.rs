@@ -0,0 +1,557 @@
+use crate::bitboard::Bitboard;
+use crate::error::C4Error;
+
+#[derive(Clone, Copy)]
+pub struct Position<const W: usize = 7, const H: usize = 6, B: Bitboard = u64> {
+    position: B,
+    mask: B,
+    moves: usize,
+}
+impl<const W: usize, const H: usize, B: Bitboard> Default for Position<W, H, B> {
+    fn default() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID;
+        Self {
+            position: B::zero(),
+            mask: B::zero(),
+            moves: 0,
+        }
+    }
+}
+impl<const W: usize, const H: usize, B: Bitboard> Position<W, H, B> {
+    pub const WIDTH: usize = W;
+    pub const HEIGHT: usize = H;
+    pub const AREA: usize = Self::WIDTH * Self::HEIGHT;
+    /// bits of `key`
+    pub const KEY_BITS: usize = Self::WIDTH * (Self::HEIGHT + 1);
+
+    /// columns ordered from the center outwards, e.g. [3, 2, 4, 1, 5, 0, 6] for width 7
+    pub const MOVE_ORDER: [usize; W] = {
+        let mut order = [0; W];
+        let mut i = 0;
+        while i < W {
+            order[i] = if i % 2 == 0 {
+                W / 2 + i.div_ceil(2)
+            } else {
+                W / 2 - i.div_ceil(2)
+            };
+            i += 1;
+        }
+        order
+    };
+
+    // every column plus its sentinel bit must fit in the bitboard,
+    // and scores (at most AREA / 2) must fit in the transposition table's i8 bound.
+    const VALID: () = assert!(W >= 1 && H >= 1 && W * (H + 1) <= B::BITS && W * H / 2 < 64);
+
+    /* bits layout (7x6):
+     * .  .  .  .  .  .  .
+     * 5 12 19 26 33 40 47
+     * 4 11 18 25 32 39 46
+     * 3 10 17 24 31 38 45
+     * 2  9 16 23 30 37 44
+     * 1  8 15 22 29 36 43
+     * 0  7 14 21 28 35 42
+     */
+
+    // masks are computed as u128 constants and truncated to B, which folds away after inlining
+    const BOTTOM_MASK: u128 = {
+        let mut m = 0;
+        let mut col = 0;
+        while col < W {
+            m |= 1 << (col * (H + 1));
+            col += 1;
+        }
+        m
+    };
+    const BOARD_MASK: u128 = Self::BOTTOM_MASK * ((1 << H) - 1);
+    #[inline(always)]
+    fn top_mask(col: usize) -> B {
+        B::one() << (col * (H + 1) + H - 1)
+    }
+    #[inline(always)]
+    fn bottom_mask(col: usize) -> B {
+        B::one() << (col * (H + 1))
+    }
+    #[inline(always)]
+    pub fn column_mask(col: usize) -> B {
+        B::from_u128((1 << H) - 1) << (col * (H + 1))
+    }
+
+    fn has_alignment(pos: B) -> bool {
+        let h = Self::HEIGHT;
+        for shift in [1, h, h + 1, h + 2] {
+            let m = pos & (pos >> shift);
+            if m & (m >> (2 * shift)) != B::zero() {
+                return true;
+            }
+        }
+        false
+    }
+    /// every cell of `pos` that is part of four (or more) in a row
+    fn alignment_cells(pos: B) -> B {
+        let h = Self::HEIGHT;
+        let mut r = B::zero();
+        for shift in [1, h, h + 1, h + 2] {
+            let m = pos & (pos >> shift);
+            let starts = m & (m >> (2 * shift));
+            r |= starts | (starts << shift) | (starts << (2 * shift)) | (starts << (3 * shift));
+        }
+        r
+    }
+
+    fn find_winning_moves(pos: B, mask: B) -> B {
+        let h = Self::HEIGHT;
+
+        // vertical;
+        let mut r = (pos << 1) & (pos << 2) & (pos << 3);
+
+        //horizontal
+        let mut p = (pos << (h + 1)) & (pos << (2 * (h + 1)));
+        r |= p & (pos << (3 * (h + 1)));
+        r |= p & (pos >> (h + 1));
+        p = (pos >> (h + 1)) & (pos >> (2 * (h + 1)));
+        r |= p & (pos << (h + 1));
+        r |= p & (pos >> (3 * (h + 1)));
+
+        //diagonal 1
+        p = (pos << h) & (pos << (2 * h));
+        r |= p & (pos << (3 * h));
+        r |= p & (pos >> h);
+        p = (pos >> h) & (pos >> (2 * h));
+        r |= p & (pos << h);
+        r |= p & (pos >> (3 * h));
+
+        //diagonal 2
+        p = (pos << (h + 2)) & (pos << (2 * (h + 2)));
+        r |= p & (pos << (3 * (h + 2)));
+        r |= p & (pos >> (h + 2));
+        p = (pos >> (h + 2)) & (pos >> (2 * (h + 2)));
+        r |= p & (pos << (h + 2));
+        r |= p & (pos >> (3 * (h + 2)));
+
+        r & (B::from_u128(Self::BOARD_MASK) ^ mask)
+    }
+
+    pub fn score_move(&self, move_bit: B) -> u32 {
+        let winning_moves = Self::find_winning_moves(self.position | move_bit, self.mask);
+        winning_moves.count_ones()
+    }
+
+    pub fn can_play(&self, col: usize) -> bool {
+        self.mask & Self::top_mask(col) == B::zero()
+    }
+
+    /// number of pieces in `col`
+    pub fn column_height(&self, col: usize) -> usize {
+        (self.mask & Self::column_mask(col)).count_ones() as usize
+    }
+    /// player (see `current_player`) owning the cell, row 0 being the bottom
+    pub fn cell(&self, col: usize, row: usize) -> Option<usize> {
+        if col >= W || row >= H {
+            return None;
+        }
+        let bit = Self::bottom_mask(col) << row;
+        if self.mask & bit == B::zero() {
+            None
+        } else if self.position & bit != B::zero() {
+            Some(self.current_player())
+        } else {
+            Some(1 - self.current_player())
+        }
+    }
+
+    pub fn play(&mut self, col: usize) {
+        self.position ^= self.mask;
+        self.mask |= self.mask + Self::bottom_mask(col);
+        self.moves += 1;
+    }
+
+    #[must_use]
+    pub fn played(&self, col: usize) -> Self {
+        let mut new = *self;
+        new.play(col);
+        new
+    }
+
+    pub fn is_winning_move(&self, col: usize) -> bool {
+        self.winning_moves() & self.possible_moves() & Self::column_mask(col) != B::zero()
+    }
+    pub fn is_forced_move(&self, col: usize) -> bool {
+        let possible_moves = self.possible_moves();
+        let opponent_winning_moves = self.opponent_winning_moves();
+        let forced_moves = possible_moves & opponent_winning_moves;
+        forced_moves & Self::column_mask(col) != B::zero()
+    }
+
+    pub const fn n_moves(&self) -> usize {
+        self.moves
+    }
+    pub const fn remaining_moves(&self) -> usize {
+        Self::AREA - self.n_moves()
+    }
+    /// 0 for the first player, 1 for the second
+    pub const fn current_player(&self) -> usize {
+        self.moves % 2
+    }
+
+    pub fn key(&self) -> B {
+        self.position + self.mask
+    }
+    /// symmetric base-3 key used by the opening book,
+    /// only fits in u64 while `n_moves() + WIDTH` is at most 40
+    pub fn key3(&self) -> u64 {
+        let mut k = 0;
+        for col in 0..Self::WIDTH {
+            self.compute_key3(&mut k, col);
+        }
+        let mut k_rev = 0;
+        for col in (0..Self::WIDTH).rev() {
+            self.compute_key3(&mut k_rev, col);
+        }
+        k.min(k_rev) / 3
+    }
+    fn compute_key3(&self, k: &mut u64, col: usize) {
+        let mut p = Self::bottom_mask(col);
+        while p & self.mask != B::zero() {
+            *k *= 3;
+            if p & self.position != B::zero() {
+                *k += 1;
+            } else {
+                *k += 2;
+            }
+            p <<= 1;
+        }
+        *k *= 3;
+    }
+
+    pub fn possible_moves(&self) -> B {
+        (self.mask + B::from_u128(Self::BOTTOM_MASK)) & B::from_u128(Self::BOARD_MASK)
+    }
+    pub fn possible_non_losing_moves(&self) -> B {
+        let mut possible_moves = self.possible_moves();
+        let opponent_winning_moves = self.opponent_winning_moves();
+        let forced_moves = possible_moves & opponent_winning_moves;
+        if forced_moves != B::zero() {
+            if forced_moves & (forced_moves - B::one()) != B::zero() {
+                // more than one forced moves, will always lose
+                return B::zero();
+            }
+            possible_moves = forced_moves;
+        }
+        possible_moves & !(opponent_winning_moves >> 1)
+    }
+    pub fn winning_moves(&self) -> B {
+        Self::find_winning_moves(self.position, self.mask)
+    }
+    pub fn opponent_winning_moves(&self) -> B {
+        Self::find_winning_moves(self.position ^ self.mask, self.mask)
+    }
+    pub fn can_win_next(&self) -> bool {
+        self.winning_moves() & self.possible_moves() != B::zero()
+    }
+
+    /// true if the player who made the last move has connected four
+    fn last_move_won(&self) -> bool {
+        Self::has_alignment(self.position ^ self.mask)
+    }
+
+    /// player (see `current_player`) who connected four, if any
+    pub fn winner(&self) -> Option<usize> {
+        if self.last_move_won() {
+            Some(1 - self.current_player())
+        } else if Self::has_alignment(self.position) {
+            // only reachable through `apply_moves`, which keeps playing after a win
+            Some(self.current_player())
+        } else {
+            None
+        }
+    }
+    pub fn is_draw(&self) -> bool {
+        self.moves == Self::AREA && self.winner().is_none()
+    }
+    pub fn is_game_over(&self) -> bool {
+        self.moves == Self::AREA || self.winner().is_some()
+    }
+    /// `Err(C4Error::GameOver)` if the game is over, for positions about to be searched
+    pub fn check_not_over(&self) -> Result<(), C4Error> {
+        if self.is_game_over() {
+            return Err(C4Error::GameOver);
+        }
+        Ok(())
+    }
+    /// bit mask of every cell belonging to a completed line of either player
+    pub fn winning_mask(&self) -> B {
+        Self::alignment_cells(self.position) | Self::alignment_cells(self.position ^ self.mask)
+    }
+    /// (col, row) of every cell belonging to a completed line, row 0 being the bottom
+    pub fn winning_cells(&self) -> Vec<(usize, usize)> {
+        let mask = self.winning_mask();
+        let mut cells = vec![];
+        for col in 0..W {
+            for row in 0..H {
+                if mask & (Self::bottom_mask(col) << row) != B::zero() {
+                    cells.push((col, row));
+                }
+            }
+        }
+        cells
+    }
+
+    /// every empty cell that would connect four for either player (whether it can be played
+    /// now or not), and who controls zugzwang
+    pub fn threats(&self) -> Threats {
+        let me = self.current_player();
+        let cells = [
+            (me, self.winning_moves()),
+            (1 - me, self.opponent_winning_moves()),
+        ];
+        let mut threats = vec![];
+        for col in 0..W {
+            for row in 0..H {
+                let bit = Self::bottom_mask(col) << row;
+                for (player, mask) in cells {
+                    if mask & bit != B::zero() {
+                        threats.push(Threat { player, col, row });
+                    }
+                }
+            }
+        }
+        threats.sort_by_key(|t| (t.player, t.col, t.row));
+
+        // with even columns, the second player can answer every move in the same column and get
+        // all the even cells, unless the first player has an odd threat the second player can't
+        // undermine from below
+        let zugzwang = H.is_multiple_of(2).then(|| {
+            let lowest = |player: usize, col: usize| {
+                threats
+                    .iter()
+                    .filter(|t| t.player == player && t.col == col)
+                    .map(|t| t.row)
+                    .min()
+            };
+            let first_controls = threats.iter().any(|t| {
+                t.player == 0 && t.is_odd() && lowest(1, t.col).is_none_or(|row| row > t.row)
+            });
+            if first_controls { 0 } else { 1 }
+        });
+        Threats { threats, zugzwang }
+    }
+
+    /// like `apply_str`, but also rejects moves after the game is won.
+    /// on error `self` is left untouched.
+    pub fn try_apply_str(&mut self, s: &str) -> Result<(), C4Error> {
+        let mut cols = Vec::with_capacity(s.len());
+        for c in s.chars() {
+            match c.to_digit(10) {
+                Some(d) if d > 0 => cols.push(d as usize - 1),
+                _ => return Err(C4Error::BadCharacter(c)),
+            }
+        }
+        self.try_apply_moves(cols)
+    }
+    /// like `apply_moves`, but also rejects moves after the game is won.
+    /// on error `self` is left untouched.
+    pub fn try_apply_moves(&mut self, it: impl IntoIterator<Item = usize>) -> Result<(), C4Error> {
+        let mut new = *self;
+        for col in it {
+            if col >= W {
+                return Err(C4Error::InvalidColumn(col));
+            }
+            if new.last_move_won() {
+                return Err(C4Error::GameOver);
+            }
+            if !new.can_play(col) {
+                return Err(C4Error::FullColumn(col));
+            }
+            new.play(col);
+        }
+        *self = new;
+        Ok(())
+    }
+
+    pub fn apply_str(&mut self, s: &str) {
+        for c in s.chars() {
+            let col = c.to_digit(10).expect("valid digit") as usize;
+            assert!(self.can_play(col - 1));
+            self.play(col - 1);
+        }
+    }
+    pub fn apply_moves(&mut self, it: impl IntoIterator<Item = usize>) {
+        for col in it {
+            assert!(self.can_play(col));
+            self.play(col)
+        }
+    }
+}
+
+/// an empty cell that would connect four for `player`, see `Position::threats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Threat {
+    /// 0 for the first player, 1 for the second
+    pub player: usize,
+    pub col: usize,
+    /// 0 being the bottom
+    pub row: usize,
+}
+impl Threat {
+    /// true on the odd rows counting from 1 at the bottom, which the first player gets when
+    /// the board fills up column by column
+    pub fn is_odd(&self) -> bool {
+        self.row.is_multiple_of(2)
+    }
+}
+
+/// see `Position::threats`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Threats {
+    /// sorted by player, column and row
+    pub threats: Vec<Threat>,
+    /// player controlling zugzwang by Allis' rules of thumb: the first player if it has an odd
+    /// threat with no threat of the second player below it in the same column, otherwise the
+    /// second player. None for boards of odd height, where these rules don't hold.
+    pub zugzwang: Option<usize>,
+}
+
+pub struct SortedMoves<const W: usize = 7> {
+    records: [(usize, u32); W],
+    n: usize,
+}
+impl<const W: usize> Default for SortedMoves<W> {
+    fn default() -> Self {
+        Self {
+            records: [(0, 0); W],
+            n: 0,
+        }
+    }
+}
+impl<const W: usize> SortedMoves<W> {
+    pub fn insert_sorted(&mut self, col: usize, score: u32) {
+        let mut pos = self.n;
+        self.n += 1;
+        while pos > 0 && self.records[pos].1 > score {
+            self.records[pos] = self.records[pos - 1];
+            pos -= 1;
+        }
+        self.records[pos] = (col, score);
+    }
+    pub fn insert(&mut self, col: usize) {
+        self.records[self.n].0 = col;
+        self.n += 1;
+    }
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        self.records[0..self.n].iter().map(|r| r.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn threats() {
+        let threat = |player, col, row| Threat { player, col, row };
+        let threats = |moves: &str| {
+            let mut p = Position::<7, 6>::default();
+            p.apply_str(moves);
+            p.threats()
+        };
+
+        let t = threats("");
+        assert!(t.threats.is_empty());
+        assert_eq!(t.zugzwang, Some(1));
+
+        // both players have three in a row on top of each other, the first one at the bottom
+        let t = threats("445566");
+        assert_eq!(
+            t.threats,
+            [
+                threat(0, 2, 0),
+                threat(0, 6, 0),
+                threat(1, 2, 1),
+                threat(1, 6, 1)
+            ]
+        );
+        assert!(t.threats[0].is_odd() && !t.threats[2].is_odd());
+        assert_eq!(t.zugzwang, Some(0));
+
+        // the second player's even threats lie below the first player's odd ones
+        let t = threats("456445566");
+        assert_eq!(
+            t.threats,
+            [
+                threat(0, 2, 2),
+                threat(0, 6, 2),
+                threat(1, 2, 1),
+                threat(1, 6, 1)
+            ]
+        );
+        assert_eq!(t.zugzwang, Some(1));
+
+        let mut p = Position::<5, 5>::default();
+        p.apply_str("223344");
+        assert_eq!(p.threats().zugzwang, None);
+    }
+
+    #[test]
+    fn try_apply() {
+        let mut p = Position::<7, 6>::default();
+        assert_eq!(p.try_apply_str("4453"), Ok(()));
+        assert_eq!(p.n_moves(), 4);
+        assert_eq!(p.try_apply_str("48"), Err(C4Error::InvalidColumn(7)));
+        assert_eq!(p.try_apply_str("40"), Err(C4Error::BadCharacter('0')));
+        assert_eq!(p.try_apply_str("4x"), Err(C4Error::BadCharacter('x')));
+        assert_eq!(p.try_apply_str("44444"), Err(C4Error::FullColumn(3)));
+        assert_eq!(p.n_moves(), 4);
+
+        let mut p = Position::<7, 6>::default();
+        assert_eq!(p.try_apply_moves([0, 1, 0, 1, 0, 1, 0]), Ok(()));
+        assert_eq!(p.try_apply_moves([2]), Err(C4Error::GameOver));
+        assert_eq!(p.n_moves(), 7);
+        // the last move may win, but the position can't be searched
+        assert_eq!(p.check_not_over(), Err(C4Error::GameOver));
+        p = Position::default();
+        assert_eq!(p.try_apply_str("1212121"), Ok(()));
+        assert_eq!(p.check_not_over(), Err(C4Error::GameOver));
+        assert_eq!(p.try_apply_str("3"), Err(C4Error::GameOver));
+    }
+
+    #[test]
+    fn game_over() {
+        let mut p = Position::<7, 6>::default();
+        p.apply_str("1212121");
+        assert_eq!(p.winner(), Some(0));
+        assert!(p.is_game_over());
+        assert!(!p.is_draw());
+        assert_eq!(p.winning_cells(), vec![(0, 0), (0, 1), (0, 2), (0, 3)]);
+
+        let mut p = Position::<7, 6>::default();
+        p.apply_str("12233434474");
+        assert_eq!(p.winner(), Some(0));
+        assert_eq!(p.winning_cells(), vec![(0, 0), (1, 1), (2, 2), (3, 3)]);
+
+        let mut p = Position::<7, 6>::default();
+        p.apply_str("1122334");
+        assert_eq!(p.winner(), Some(0));
+        assert_eq!(p.winning_cells(), vec![(0, 0), (1, 0), (2, 0), (3, 0)]);
+        assert_eq!(p.cell(0, 0), Some(0));
+        assert_eq!(p.cell(0, 1), Some(1));
+        assert_eq!(p.cell(3, 0), Some(0));
+        assert_eq!(p.cell(3, 1), None);
+        assert_eq!(p.cell(7, 0), None);
+        assert_eq!(p.column_height(0), 2);
+        assert_eq!(p.column_height(3), 1);
+        assert_eq!(p.column_height(4), 0);
+
+        let mut p = Position::<7, 6>::default();
+        p.apply_str("112233");
+        assert_eq!(p.winner(), None);
+        assert!(!p.is_game_over());
+        assert!(p.winning_cells().is_empty());
+
+        let mut p = Position::<4, 4>::default();
+        p.apply_str("1234123421433412");
+        assert_eq!(p.winner(), None);
+        assert!(p.is_draw());
+    }
+}
@@ -0,0 +1,127 @@
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::bitboard::Bitboard;
+use crate::lookup::TranspositionTable;
+use crate::outcome::{GameResult, Outcome};
+use crate::position::Position;
+use crate::solver::Solver;
+
+/// random positions tried by `Puzzle::generate` before giving up
+const MAX_TRIES: usize = 1000;
+
+/// a position where the player to move has a single winning column, winning in `win_in` moves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    /// the moves leading to the position, for `Position::apply_str`
+    pub moves: String,
+    /// 0-based column of the winning move
+    pub solution: usize,
+    /// moves of the player to move until the game is won, the winning one included
+    pub win_in: usize,
+}
+
+impl Puzzle {
+    /// plays random games until one reaches a "win in `win_in`" position, checked with
+    /// `Solver::analyze`. the same seed and solver settings give the same puzzle.
+    /// returns None if no puzzle was found after `MAX_TRIES` positions, or if a search was
+    /// interrupted.
+    pub fn generate<const W: usize, const H: usize, B: Bitboard, T: TranspositionTable<B>>(
+        solver: &mut Solver<W, H, B, T>,
+        win_in: usize,
+        seed: u64,
+    ) -> Option<Self> {
+        assert!(W <= 9, "move strings have one digit per move");
+        if win_in == 0 {
+            return None;
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+        // the winner plays `win_in` moves and the loser one less
+        let last = (W * H + 1).checked_sub(2 * win_in)?;
+        // early positions take long to solve
+        let first = (W * H / 2).min(last);
+        for _ in 0..MAX_TRIES {
+            let n = rng.random_range(first..=last);
+            let Some(moves) = random_game::<W, H, B>(&mut rng, n) else {
+                continue;
+            };
+            let mut position = Position::<W, H, B>::default();
+            position.apply_moves(moves.iter().copied());
+            if win_in > 1 && position.can_win_next() {
+                continue;
+            }
+
+            let scores = solver.analyze(&position, false);
+            if solver.is_interrupted() {
+                return None;
+            }
+            // scores are for the opponent
+            let winning: Vec<usize> = (0..W)
+                .filter(|&col| scores[col].is_some_and(|s| s < 0))
+                .collect();
+            let &[solution] = winning.as_slice() else {
+                continue;
+            };
+            let score = -scores[solution]?;
+            let outcome = Outcome::new(&position, score);
+            if outcome.result == GameResult::Win && outcome.plies.div_ceil(2) == win_in {
+                return Some(Self {
+                    moves: moves.iter().map(|col| (col + 1).to_string()).collect(),
+                    solution,
+                    win_in,
+                });
+            }
+        }
+        None
+    }
+}
+
+/// `n` random moves, none of them ending the game. None if the game can't go on that long.
+fn random_game<const W: usize, const H: usize, B: Bitboard>(
+    rng: &mut StdRng,
+    n: usize,
+) -> Option<Vec<usize>> {
+    let mut position = Position::<W, H, B>::default();
+    let mut moves = Vec::with_capacity(n);
+    for _ in 0..n {
+        let cols: Vec<usize> = (0..W)
+            .filter(|&col| position.can_play(col) && !position.is_winning_move(col))
+            .collect();
+        let &col = cols.choose(rng)?;
+        position.play(col);
+        moves.push(col);
+    }
+    Some(moves)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    type Position = crate::position::Position<7, 6>;
+    type Solver = crate::solver::Solver<7, 6>;
+
+    #[test]
+    fn generate() {
+        let mut solver = Solver::default();
+        for win_in in [1, 3] {
+            let puzzle = Puzzle::generate(&mut solver, win_in, 7).unwrap();
+            assert_eq!(puzzle.win_in, win_in);
+            let mut position = Position::default();
+            position.try_apply_str(&puzzle.moves).unwrap();
+            assert!(!position.is_game_over());
+
+            let scores = solver.analyze(&position, false);
+            for (col, score) in scores.iter().enumerate() {
+                let wins = score.is_some_and(|s| s < 0);
+                assert_eq!(wins, col == puzzle.solution, "{}", puzzle.moves);
+            }
+            let outcome = Outcome::new(&position, -scores[puzzle.solution].unwrap());
+            assert_eq!(outcome.plies.div_ceil(2), win_in);
+            assert_eq!(Puzzle::generate(&mut solver, win_in, 7), Some(puzzle));
+        }
+        assert_eq!(Puzzle::generate(&mut solver, 0, 7), None);
+        assert_eq!(Puzzle::generate(&mut solver, 22, 7), None);
+    }
+}
@@ -0,0 +1,1279 @@
+use std::collections::HashSet;
+use std::hint::unreachable_unchecked;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::bitboard::Bitboard;
+use crate::error::{BookError, C4Error, TableError};
+use crate::lookup::{MRUTable, OpeningBook, TranspositionTable};
+use crate::position::Position;
+
+struct Bound(u8);
+impl Bound {
+    fn new_upper(v: i32) -> Self {
+        let v = ((v as i8) << 1) as u8;
+        Self(v)
+    }
+    fn new_lower(v: i32) -> Self {
+        let v = ((v as i8) << 1) as u8;
+        Self(v | 1)
+    }
+    fn value(&self) -> i32 {
+        ((self.0 as i8) >> 1) as i32
+    }
+    fn is_lower(&self) -> bool {
+        self.0 & 1 == 1
+    }
+    fn is_upper(&self) -> bool {
+        self.0 & 1 == 0
+    }
+}
+
+/// score interval known after a possibly interrupted search, `min == max` once solved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScoreBounds {
+    pub min: i32,
+    pub max: i32,
+}
+impl ScoreBounds {
+    pub fn exact(score: i32) -> Self {
+        Self {
+            min: score,
+            max: score,
+        }
+    }
+    pub fn is_exact(&self) -> bool {
+        self.min == self.max
+    }
+    /// the exact score, or the value closest to a draw within the bounds
+    pub fn score(&self) -> i32 {
+        0.clamp(self.min, self.max)
+    }
+}
+/// the bounds from the other player's point of view
+impl std::ops::Neg for ScoreBounds {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self {
+            min: -self.max,
+            max: -self.min,
+        }
+    }
+}
+
+/// how a move compares to the best one, see `Solver::review_game`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveClass {
+    /// scores as well as the best move
+    Best,
+    /// keeps the result (win, draw or loss) but wins slower or loses faster
+    Inaccuracy,
+    /// turns a draw into a loss
+    Blunder,
+    /// turns a win into a draw or a loss
+    MissedWin,
+}
+impl MoveClass {
+    /// `score` of the move and `best` score, from the point of view of the player to move
+    pub fn new(score: i32, best: i32) -> Self {
+        if score >= best {
+            Self::Best
+        } else if best > 0 && score <= 0 {
+            Self::MissedWin
+        } else if best == 0 {
+            Self::Blunder
+        } else {
+            Self::Inaccuracy
+        }
+    }
+}
+
+/// a move of a game reviewed by `Solver::review_game`,
+/// scores are from the point of view of the player who moved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveReview {
+    pub col: usize,
+    pub score: i32,
+    /// score of the best moves of the position
+    pub best_score: i32,
+    /// every column scoring `best_score`
+    pub best_moves: Vec<usize>,
+    pub class: MoveClass,
+    /// false if a search was interrupted and the scores are only guesses
+    pub exact: bool,
+}
+
+/// counters of the last `solve`, `analyze` or `principal_variation` call
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// positions visited by `negamax`
+    pub nodes: u64,
+    pub table_hits: u64,
+    pub table_misses: u64,
+    /// entries of other positions replaced in the transposition table
+    pub table_overwrites: u64,
+    pub book_hits: u64,
+    pub elapsed: Duration,
+    /// deepest ply reached below the position given to the call
+    pub max_depth: usize,
+}
+
+/// limits are checked every this many nodes
+const LIMIT_CHECK_INTERVAL: u64 = 1 << 12;
+
+const TABLE_MAGIC: &[u8; 4] = b"C4TT";
+const TABLE_VERSION: u8 = 2;
+/// magic, version, width, height, table format, bytes per entry,
+/// then the number of entries as u64
+const TABLE_HEADER_LEN: usize = 17;
+
+pub struct Solver<
+    const W: usize = 7,
+    const H: usize = 6,
+    B: Bitboard = u64,
+    T: TranspositionTable<B> = MRUTable<B, <B as Bitboard>::PartialKey, u8>,
+> {
+    table: T,
+    book: Option<Arc<OpeningBook>>,
+    stop: Arc<AtomicBool>,
+    time_limit: Option<Duration>,
+    node_limit: Option<u64>,
+    deadline: Option<Instant>,
+    interrupted: bool,
+    stats: SearchStats,
+    started: Option<Instant>,
+    root_moves: usize,
+    _phantom: PhantomData<B>,
+}
+impl<const W: usize, const H: usize, B: Bitboard, T: TranspositionTable<B>> Default
+    for Solver<W, H, B, T>
+{
+    fn default() -> Self {
+        Self::with_table(T::default())
+    }
+}
+impl<const W: usize, const H: usize, B: Bitboard, T: TranspositionTable<B>> Solver<W, H, B, T> {
+    /// a solver whose transposition table takes at most about `bytes` of memory, or the least
+    /// the table needs to tell positions apart
+    pub fn with_table_bytes(bytes: usize) -> Self {
+        Self::with_table(T::with_bytes(bytes, Position::<W, H, B>::KEY_BITS))
+    }
+    fn with_table(table: T) -> Self {
+        Self {
+            table,
+            book: None,
+            stop: Arc::default(),
+            time_limit: None,
+            node_limit: None,
+            deadline: None,
+            interrupted: false,
+            stats: SearchStats::default(),
+            started: None,
+            root_moves: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// replaces the transposition table by an empty one, see `with_table_bytes`
+    pub fn set_table_bytes(&mut self, bytes: usize) {
+        // free the old table first, both may not fit in memory
+        self.table = T::with_bytes(0, 0);
+        self.table = T::with_bytes(bytes, Position::<W, H, B>::KEY_BITS);
+    }
+    /// forgets every position searched so far, e.g. between games
+    pub fn clear_table(&mut self) {
+        self.table.clear();
+    }
+
+    /// book consulted before searching shallow positions, None to search without one.
+    /// fails if the book is for another board size.
+    pub fn set_opening_book(&mut self, book: Option<Arc<OpeningBook>>) -> Result<(), BookError> {
+        if let Some(book) = &book
+            && book.size() != (W, H)
+        {
+            return Err(BookError::WrongSize {
+                expected: (W, H),
+                found: book.size(),
+            });
+        }
+        self.book = book;
+        Ok(())
+    }
+    pub fn opening_book(&self) -> Option<&Arc<OpeningBook>> {
+        self.book.as_ref()
+    }
+
+    pub fn save_table(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        std::fs::write(path, self.table_to_bytes())
+    }
+    /// fails unless the table was saved by a solver with the same board and table
+    pub fn load_table(&mut self, path: impl AsRef<Path>) -> Result<(), TableError> {
+        self.load_table_bytes(&std::fs::read(path)?)
+    }
+
+    /// the transposition table with a header, for `load_table_bytes` in a later session
+    pub fn table_to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(TABLE_HEADER_LEN + T::ENTRY_BYTES * self.table.size());
+        bytes.extend(TABLE_MAGIC);
+        bytes.extend([
+            TABLE_VERSION,
+            W as u8,
+            H as u8,
+            T::FORMAT,
+            T::ENTRY_BYTES as u8,
+        ]);
+        bytes.extend((self.table.size() as u64).to_le_bytes());
+        self.table.save(&mut bytes);
+        bytes
+    }
+    /// replaces the transposition table, keeping it if `bytes` is not a table of the same
+    /// board size, entry type and size
+    pub fn load_table_bytes(&mut self, bytes: &[u8]) -> Result<(), TableError> {
+        let Some((header, entries)) = bytes.split_at_checked(TABLE_HEADER_LEN) else {
+            return Err(TableError::BadHeader("too short"));
+        };
+        if &header[..4] != TABLE_MAGIC {
+            return Err(TableError::BadHeader("not a table"));
+        }
+        let [version, w, h, format, entry_bytes] = std::array::from_fn(|i| header[4 + i] as usize);
+        if version != TABLE_VERSION as usize {
+            return Err(TableError::BadHeader("unsupported version"));
+        }
+        if (w, h) != (W, H) {
+            return Err(TableError::WrongSize {
+                expected: (W, H),
+                found: (w, h),
+            });
+        }
+        if format != T::FORMAT as usize || entry_bytes != T::ENTRY_BYTES {
+            return Err(TableError::BadHeader("different table type"));
+        }
+        let size = u64::from_le_bytes(header[9..].try_into().unwrap()) as usize;
+        if size != self.table.size() {
+            return Err(TableError::WrongTableSize {
+                expected: self.table.size(),
+                found: size,
+            });
+        }
+        if entries.len() != size * entry_bytes {
+            return Err(TableError::Truncated {
+                expected: size * entry_bytes,
+                found: entries.len(),
+            });
+        }
+        self.table.load(entries);
+        Ok(())
+    }
+
+    /// handle to stop running searches from another thread, which then return partial results.
+    /// the flag is not cleared by the solver, searches started while it is set return at once.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        self.stop.clone()
+    }
+    /// share a stop flag, e.g. between several solvers
+    pub fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = stop;
+    }
+    /// wall time allowed for each `solve` or `analyze` call
+    pub fn set_time_limit(&mut self, limit: Option<Duration>) {
+        self.time_limit = limit;
+    }
+    /// nodes allowed for each `solve` or `analyze` call
+    pub fn set_node_limit(&mut self, limit: Option<u64>) {
+        self.node_limit = limit;
+    }
+    /// true if the last `solve` or `analyze` call was cut short by a limit or the stop flag
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+    pub fn last_stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    fn start_search(&mut self, position: &Position<W, H, B>) {
+        let now = Instant::now();
+        self.stats = SearchStats::default();
+        self.table.new_search();
+        self.started = Some(now);
+        self.root_moves = position.n_moves();
+        self.interrupted = self.stop.load(Ordering::Relaxed);
+        self.deadline = self.time_limit.map(|limit| now + limit);
+    }
+    fn finish_search(&mut self) {
+        if let Some(started) = self.started.take() {
+            self.stats.elapsed = started.elapsed();
+        }
+    }
+    fn check_limits(&mut self) {
+        self.interrupted = self.stop.load(Ordering::Relaxed)
+            || self
+                .node_limit
+                .is_some_and(|limit| self.stats.nodes >= limit)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline);
+    }
+
+    /// the returned score is meaningless once `self.interrupted` is set
+    fn negamax(&mut self, position: &Position<W, H, B>, mut alpha: i32, mut beta: i32) -> i32 {
+        self.stats.nodes += 1;
+        let depth = position.n_moves() - self.root_moves;
+        self.stats.max_depth = self.stats.max_depth.max(depth);
+        if self.stats.nodes.is_multiple_of(LIMIT_CHECK_INTERVAL) {
+            self.check_limits();
+        }
+        if self.interrupted {
+            return alpha;
+        }
+
+        let possible_non_losing_moves = position.possible_non_losing_moves();
+        if possible_non_losing_moves == B::zero() {
+            return -(position.remaining_moves() as i32) / 2;
+        }
+        if position.remaining_moves() <= 2 {
+            return 0;
+        }
+
+        let bound = self.table.get(position.key()).map(Bound);
+        if bound.is_some() {
+            self.stats.table_hits += 1;
+        } else {
+            self.stats.table_misses += 1;
+        }
+        let (min, max) = match bound {
+            None => (
+                -((position.remaining_moves() - 2) as i32) / 2,
+                (position.remaining_moves() - 1) as i32 / 2,
+            ),
+            Some(b) if b.is_lower() => (b.value(), (position.remaining_moves() - 1) as i32 / 2),
+            Some(b) if b.is_upper() => (-((position.remaining_moves() - 2) as i32) / 2, b.value()),
+            // safety: is_lower() || is_upper() === true
+            _ => unsafe { unreachable_unchecked() },
+        };
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+
+        if let Some(score) = self.book.as_ref().and_then(|book| book.get(position)) {
+            self.stats.book_hits += 1;
+            return score;
+        }
+
+        let sort_moves = position.n_moves() <= Position::<W, H, B>::AREA / 3;
+        let mut moves = crate::position::SortedMoves::<W>::default();
+        for col in Position::<W, H, B>::MOVE_ORDER {
+            let move_bit = possible_non_losing_moves & Position::<W, H, B>::column_mask(col);
+            if move_bit != B::zero() {
+                if sort_moves {
+                    moves.insert_sorted(col, position.score_move(move_bit));
+                } else {
+                    moves.insert(col);
+                }
+            }
+        }
+        for col in moves.iter() {
+            let score = -self.negamax(&position.played(col), -beta, -alpha);
+            if self.interrupted {
+                // don't store bounds derived from an unfinished search
+                return alpha;
+            }
+            if score >= beta {
+                self.put(position, Bound::new_lower(score));
+                return score;
+            }
+            if score > alpha {
+                alpha = score;
+            }
+        }
+
+        self.put(position, Bound::new_upper(alpha));
+        alpha
+    }
+
+    fn put(&mut self, position: &Position<W, H, B>, bound: Bound) {
+        if self
+            .table
+            .put_with_depth(position.key(), bound.0, position.remaining_moves())
+        {
+            self.stats.table_overwrites += 1;
+        }
+    }
+
+    /// the exact score, or the best guess (see `ScoreBounds::score`) if interrupted
+    pub fn solve(&mut self, position: &Position<W, H, B>, weak: bool) -> i32 {
+        self.solve_bounds(position, weak).score()
+    }
+
+    /// like `solve`, but tells how far the search got if interrupted
+    pub fn solve_bounds(&mut self, position: &Position<W, H, B>, weak: bool) -> ScoreBounds {
+        self.start_search(position);
+        let bounds = self.search(position, weak);
+        self.finish_search();
+        bounds
+    }
+
+    fn search(&mut self, position: &Position<W, H, B>, weak: bool) -> ScoreBounds {
+        if position.can_win_next() {
+            return ScoreBounds::exact((position.remaining_moves() + 1) as i32 / 2);
+        }
+        let (mut min, mut max) = if weak {
+            (-1, 1)
+        } else {
+            let n = position.remaining_moves() as i32;
+            (-n / 2, (n + 1) / 2)
+        };
+        while min < max {
+            let mut m = min + (max - min) / 2;
+            if m <= 0 && m > min / 2 {
+                m = min / 2;
+            } else if m >= 0 && m < max / 2 {
+                m = max / 2
+            };
+            let score = self.negamax(position, m, m + 1);
+            if self.interrupted {
+                break;
+            }
+            // a weak search can find a bound beyond its window, keep min <= max
+            if score <= m {
+                max = score.max(min);
+            } else {
+                min = score.min(max);
+            }
+        }
+        ScoreBounds { min, max }
+    }
+
+    /// scores of every playable column from the opponent's point of view, see `solve`.
+    /// a winning move scores like any move the opponent loses after, negatively.
+    pub fn analyze(&mut self, position: &Position<W, H, B>, weak: bool) -> [Option<i32>; W] {
+        self.analyze_bounds(position, weak)
+            .map(|bounds| bounds.map(|b| b.score()))
+    }
+
+    /// like `analyze`, but tells how far the search got if interrupted
+    pub fn analyze_bounds(
+        &mut self,
+        position: &Position<W, H, B>,
+        weak: bool,
+    ) -> [Option<ScoreBounds>; W] {
+        self.start_search(position);
+        let bounds = std::array::from_fn(|col| {
+            if !position.can_play(col) {
+                None
+            } else if position.is_winning_move(col) {
+                // the opponent lost
+                Some(ScoreBounds::exact(
+                    -((position.remaining_moves() + 1) as i32 / 2),
+                ))
+            } else {
+                Some(self.search(&position.played(col), weak))
+            }
+        });
+        self.finish_search();
+        bounds
+    }
+
+    /// a table for `analyze_parallel_with`, e.g. `Arc<SharedTable<B>>`, taking about as much
+    /// memory as the table of this solver
+    pub fn parallel_table<S: TranspositionTable<B>>(&self) -> S {
+        S::with_bytes(self.table.size() * T::ENTRY_BYTES, Position::<W, H, B>::KEY_BITS)
+    }
+
+    /// `analyze_parallel` for tables that can't be shared between threads: the columns share
+    /// `table`, usually made by `parallel_table` and kept across calls like the solver's own.
+    pub fn analyze_parallel_with<S: TranspositionTable<B> + Clone + Send>(
+        &mut self,
+        table: &S,
+        position: &Position<W, H, B>,
+        weak: bool,
+    ) -> [Option<ScoreBounds>; W] {
+        let mut solver = Solver::<W, H, B, S>::with_table(table.clone());
+        solver.book = self.book.clone();
+        solver.stop = self.stop.clone();
+        solver.time_limit = self.time_limit;
+        solver.node_limit = self.node_limit;
+        let bounds = solver.analyze_parallel(position, weak);
+        self.stats = solver.stats;
+        self.interrupted = solver.interrupted;
+        bounds
+    }
+
+    /// columns of an optimal line of play from `position` to the end of the game,
+    /// both sides playing perfectly. the losing side delays its loss as long as possible.
+    /// truncated if the search is interrupted.
+    pub fn principal_variation(&mut self, position: &Position<W, H, B>) -> Vec<usize> {
+        self.start_search(position);
+        let mut line = vec![];
+        let mut p = *position;
+        while !p.is_game_over() {
+            if let Some(col) = (0..W).find(|&col| p.can_play(col) && p.is_winning_move(col)) {
+                line.push(col);
+                break;
+            }
+            // children are cheap to solve once the table is warmed up by the parent
+            let score = self.search(&p, false);
+            let mut best = None;
+            for col in Position::<W, H, B>::MOVE_ORDER {
+                if self.interrupted {
+                    break;
+                }
+                if p.can_play(col) && -self.search(&p.played(col), false).score() == score.score() {
+                    best = Some(col);
+                    break;
+                }
+            }
+            let Some(col) = best.filter(|_| !self.interrupted) else {
+                break;
+            };
+            line.push(col);
+            p.play(col);
+        }
+        self.finish_search();
+        line
+    }
+
+    /// replays `moves` (0-based columns) from the start of a game, comparing each move to the
+    /// best ones of its position. every position is solved strongly, which takes long early in
+    /// the game without an opening book.
+    pub fn review_game(&mut self, moves: &[usize]) -> Result<Vec<MoveReview>, C4Error> {
+        let mut position = Position::<W, H, B>::default();
+        position.try_apply_moves(moves.iter().copied())?;
+
+        let mut position = Position::<W, H, B>::default();
+        let mut reviews = Vec::with_capacity(moves.len());
+        for &col in moves {
+            let scores: Vec<Option<ScoreBounds>> = self
+                .analyze_bounds(&position, false)
+                .into_iter()
+                .map(|b| b.map(|b| -b))
+                .collect();
+            let best_score = scores.iter().flatten().map(|b| b.score()).max();
+            let best_score = best_score.expect("the move list was checked");
+            let best_moves: Vec<usize> = (0..W)
+                .filter(|&c| scores[c].is_some_and(|b| b.score() == best_score))
+                .collect();
+            let bounds = scores[col].expect("the move list was checked");
+            reviews.push(MoveReview {
+                col,
+                score: bounds.score(),
+                best_score,
+                class: MoveClass::new(bounds.score(), best_score),
+                exact: scores.iter().flatten().all(|b| b.is_exact()),
+                best_moves,
+            });
+            position.play(col);
+        }
+        Ok(reviews)
+    }
+
+    /// solves every position of at most `depth` moves that is not over yet, making a book for
+    /// `set_opening_book` with a table of about `1 << log_size` entries.
+    /// positions whose search is interrupted are left out.
+    /// `progress` is called with the number of positions solved and the total.
+    pub fn generate_opening_book(
+        &mut self,
+        depth: usize,
+        log_size: usize,
+        mut progress: impl FnMut(usize, usize),
+    ) -> OpeningBook {
+        let mut book = OpeningBook::new(W, H, depth, log_size);
+        // positions by number of moves, deduplicated with the symmetric key3
+        let mut layers = vec![vec![Position::<W, H, B>::default()]];
+        let mut seen = HashSet::new();
+        while layers.len() <= depth {
+            let mut next = vec![];
+            for position in layers.last().unwrap() {
+                for col in 0..W {
+                    if !position.can_play(col) {
+                        continue;
+                    }
+                    let child = position.played(col);
+                    if !child.is_game_over() && seen.insert(child.key3()) {
+                        next.push(child);
+                    }
+                }
+            }
+            layers.push(next);
+        }
+
+        let total = layers.iter().map(Vec::len).sum();
+        let mut done = 0;
+        // deep positions first, their table entries speed up the shallow ones
+        for position in layers.iter().rev().flatten() {
+            let score = self.solve(position, false);
+            if !self.interrupted {
+                book.insert(position, score);
+            }
+            done += 1;
+            progress(done, total);
+        }
+        book
+    }
+}
+
+impl<const W: usize, const H: usize, B: Bitboard, T: TranspositionTable<B> + Clone + Send>
+    Solver<W, H, B, T>
+{
+    /// like `analyze_bounds`, but every column is solved on its own thread.
+    /// threads only help each other if cloning `T` shares the table, e.g. `Arc<SharedTable<B>>`.
+    /// the node limit applies to each thread.
+    pub fn analyze_parallel(
+        &mut self,
+        position: &Position<W, H, B>,
+        weak: bool,
+    ) -> [Option<ScoreBounds>; W] {
+        self.start_search(position);
+        let results = std::thread::scope(|scope| {
+            let handles: [_; W] = std::array::from_fn(|col| {
+                if !position.can_play(col) || position.is_winning_move(col) {
+                    return None;
+                }
+                let mut worker = self.fork();
+                let child = position.played(col);
+                Some(scope.spawn(move || {
+                    let bounds = worker.search(&child, weak);
+                    (bounds, worker.stats, worker.interrupted)
+                }))
+            });
+            handles.map(|h| h.map(|h| h.join().expect("solver thread panicked")))
+        });
+
+        let mut bounds = [None; W];
+        for (col, result) in results.into_iter().enumerate() {
+            if let Some((b, stats, interrupted)) = result {
+                bounds[col] = Some(b);
+                self.stats.nodes += stats.nodes;
+                self.stats.table_hits += stats.table_hits;
+                self.stats.table_misses += stats.table_misses;
+                self.stats.table_overwrites += stats.table_overwrites;
+                self.stats.book_hits += stats.book_hits;
+                self.stats.max_depth = self.stats.max_depth.max(stats.max_depth);
+                self.interrupted |= interrupted;
+            } else if position.can_play(col) {
+                // a winning move, the opponent lost
+                bounds[col] = Some(ScoreBounds::exact(
+                    -((position.remaining_moves() + 1) as i32 / 2),
+                ));
+            }
+        }
+        self.finish_search();
+        bounds
+    }
+
+    /// a solver sharing the table, book, stop flag and limits of the current search
+    fn fork(&self) -> Self {
+        Self {
+            table: self.table.clone(),
+            book: self.book.clone(),
+            stop: self.stop.clone(),
+            time_limit: self.time_limit,
+            node_limit: self.node_limit,
+            deadline: self.deadline,
+            interrupted: self.interrupted,
+            stats: SearchStats::default(),
+            started: None,
+            root_moves: self.root_moves,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    type Position = crate::position::Position<7, 6>;
+    type Solver = super::Solver<7, 6>;
+
+    fn negamax_reference<const W: usize, const H: usize, B: crate::bitboard::Bitboard>(
+        position: &crate::position::Position<W, H, B>,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        if position.remaining_moves() == 0 {
+            return 0;
+        }
+        if position.can_win_next() {
+            return (position.remaining_moves() + 1) as i32 / 2;
+        }
+        let max = (position.remaining_moves() - 1) as i32 / 2;
+        if beta > max {
+            beta = max;
+            if alpha >= beta {
+                return beta;
+            }
+        }
+        for col in crate::position::Position::<W, H, B>::MOVE_ORDER {
+            if position.can_play(col) {
+                let mut new_position = *position;
+                new_position.play(col);
+                let score = -negamax_reference(&new_position, -beta, -alpha);
+                if score >= beta {
+                    return score;
+                }
+                if score > alpha {
+                    alpha = score;
+                }
+            }
+        }
+        alpha
+    }
+
+    fn all_moves<const W: usize, const H: usize>() -> Vec<usize> {
+        let mut v = vec![];
+        for x in 0..W {
+            for _ in 0..H {
+                v.push(x)
+            }
+        }
+        v
+    }
+
+    fn test_correctness<const W: usize, const H: usize, B: crate::bitboard::Bitboard>(
+        solver: &mut super::Solver<W, H, B>,
+        moves: &[usize],
+    ) {
+        let mut p = crate::position::Position::<W, H, B>::default();
+        let code = moves
+            .iter()
+            .copied()
+            .map(|c| char::from_digit(c as u32, 10).unwrap())
+            .collect::<String>();
+        p.apply_moves(moves.iter().copied());
+        eprint!("{code} ");
+        let answer = solver.solve(&p, false);
+        let reference = negamax_reference(&p, -100, 100);
+        eprintln!("{answer} {reference}");
+        assert_eq!(answer, reference);
+    }
+
+    #[test]
+    fn random_endgame() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut solver = Solver::default();
+        let mut all_moves = all_moves::<7, 6>();
+        for _ in 0..100 {
+            all_moves.shuffle(rng);
+            let min = Position::AREA / 3 * 2;
+            let max = Position::AREA;
+            let range = min..max;
+            let moves = &all_moves[0..rng.random_range(range)];
+            test_correctness(&mut solver, moves);
+        }
+    }
+
+    #[test]
+    fn random_endgame_6x5() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut solver = super::Solver::<6, 5>::default();
+        let mut all_moves = all_moves::<6, 5>();
+        for _ in 0..100 {
+            all_moves.shuffle(rng);
+            let moves = &all_moves[0..rng.random_range(15..30)];
+            test_correctness(&mut solver, moves);
+        }
+    }
+
+    #[test]
+    fn random_endgame_9x7() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut solver = super::Solver::<9, 7, u128>::default();
+        let mut all_moves = all_moves::<9, 7>();
+        for _ in 0..20 {
+            all_moves.shuffle(rng);
+            let moves = &all_moves[0..rng.random_range(50..63)];
+            test_correctness(&mut solver, moves);
+        }
+    }
+
+    #[test]
+    fn random_endgame_8x8() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut solver = super::Solver::<8, 8, u128>::default();
+        let mut all_moves = all_moves::<8, 8>();
+        for _ in 0..20 {
+            all_moves.shuffle(rng);
+            let moves = &all_moves[0..rng.random_range(50..64)];
+            test_correctness(&mut solver, moves);
+        }
+    }
+
+    #[test]
+    fn wide_bitboard_agrees() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut narrow = Solver::default();
+        let mut wide = super::Solver::<7, 6, u128>::default();
+        let mut all_moves = all_moves::<7, 6>();
+        for _ in 0..100 {
+            all_moves.shuffle(rng);
+            let moves = &all_moves[0..rng.random_range(Position::AREA / 2..Position::AREA)];
+            let mut p = Position::default();
+            p.apply_moves(moves.iter().copied());
+            let mut q = crate::position::Position::<7, 6, u128>::default();
+            q.apply_moves(moves.iter().copied());
+            assert_eq!(p.key() as u128, q.key());
+            assert_eq!(p.key3(), q.key3());
+            assert_eq!(narrow.solve(&p, false), wide.solve(&q, false));
+        }
+    }
+
+    #[test]
+    fn interrupted() {
+        let mut solver = Solver::default();
+        let p = Position::default();
+
+        solver.set_node_limit(Some(10_000));
+        let bounds = solver.solve_bounds(&p, false);
+        assert!(solver.is_interrupted());
+        assert!(bounds.min < bounds.max);
+        assert!(bounds.min <= 1 && 1 <= bounds.max);
+
+        solver.set_node_limit(None);
+        solver.set_time_limit(Some(std::time::Duration::from_millis(10)));
+        let started = std::time::Instant::now();
+        solver.analyze(&p, false);
+        assert!(solver.is_interrupted());
+        assert!(started.elapsed() < std::time::Duration::from_secs(1));
+
+        solver.set_time_limit(None);
+        solver
+            .stop_flag()
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        solver.solve(&p, false);
+        assert!(solver.is_interrupted());
+        solver
+            .stop_flag()
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        let mut p = Position::default();
+        p.apply_str("5554224333234511764415115");
+        solver.set_node_limit(Some(1 << 30));
+        let bounds = solver.solve_bounds(&p, false);
+        assert!(!solver.is_interrupted());
+        assert!(bounds.is_exact());
+    }
+
+    #[test]
+    fn review_game() {
+        let mut solver = super::Solver::<5, 4>::default();
+        let moves = solver.principal_variation(&crate::position::Position::default());
+        let reviews = solver.review_game(&moves).unwrap();
+        assert_eq!(reviews.len(), moves.len());
+        for (review, &col) in reviews.iter().zip(&moves) {
+            assert_eq!(review.col, col);
+            assert_eq!(review.class, super::MoveClass::Best);
+            assert!(review.best_moves.contains(&col));
+            assert!(review.exact);
+        }
+
+        // 5x4 is a draw, unless the first player starts on a side column (0-based 0 or 4),
+        // then the second player wins by playing next to it
+        let reviews = solver.review_game(&[0, 2, 2]).unwrap();
+        assert_eq!(reviews[0].best_moves, [1, 2, 3]);
+        assert_eq!(reviews[0].class, super::MoveClass::Blunder);
+        assert!(reviews[1].best_score > 0);
+        assert_eq!(reviews[1].best_moves, [1]);
+        assert_eq!(reviews[1].class, super::MoveClass::MissedWin);
+        for review in &reviews {
+            assert_eq!(
+                review.class,
+                super::MoveClass::new(review.score, review.best_score)
+            );
+        }
+
+        assert_eq!(
+            solver.review_game(&[5]),
+            Err(crate::error::C4Error::InvalidColumn(5))
+        );
+    }
+
+    #[test]
+    fn move_class() {
+        assert_eq!(super::MoveClass::new(3, 3), super::MoveClass::Best);
+        assert_eq!(super::MoveClass::new(2, 3), super::MoveClass::Inaccuracy);
+        assert_eq!(super::MoveClass::new(-3, -2), super::MoveClass::Inaccuracy);
+        assert_eq!(super::MoveClass::new(0, 3), super::MoveClass::MissedWin);
+        assert_eq!(super::MoveClass::new(-1, 3), super::MoveClass::MissedWin);
+        assert_eq!(super::MoveClass::new(-1, 0), super::MoveClass::Blunder);
+    }
+
+    #[test]
+    fn principal_variation() {
+        let mut solver = Solver::default();
+        for line in include_str!("Test_L2_R1").lines().take(20) {
+            let (moves, score) = line.split_once(' ').unwrap();
+            let score = score.parse::<i32>().unwrap();
+            let mut p = Position::default();
+            p.apply_str(moves);
+            let pv = solver.principal_variation(&p);
+            let player = p.current_player();
+            let mut expected = score;
+            for &col in &pv {
+                assert!(!p.is_game_over());
+                assert_eq!(solver.solve(&p, false), expected);
+                p.play(col);
+                expected = -expected;
+            }
+            assert!(p.is_game_over());
+            match score.signum() {
+                1 => assert_eq!(p.winner(), Some(player)),
+                -1 => assert_eq!(p.winner(), Some(1 - player)),
+                _ => assert!(p.is_draw()),
+            }
+            // the game ends exactly when the score says it does
+            if score != 0 {
+                assert_eq!(score.abs(), (p.remaining_moves() as i32 + 2) / 2);
+            }
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let mut solver = Solver::default();
+        let mut p = Position::default();
+        p.apply_str("52753311433677442422121");
+        solver.solve(&p, false);
+        let stats = solver.last_stats();
+        assert!(stats.nodes > 0);
+        assert!(stats.table_misses > 0);
+        assert!(stats.max_depth > 0 && stats.max_depth <= p.remaining_moves());
+        assert_eq!(stats.book_hits, 0);
+
+        // solving again is answered from the table
+        solver.solve(&p, false);
+        let again = solver.last_stats();
+        assert!(again.nodes < stats.nodes);
+        assert!(again.table_hits > 0);
+
+        // stats are reset per call
+        p.apply_str("5");
+        solver.analyze(&p, false);
+        assert_ne!(solver.last_stats(), again);
+    }
+
+    #[test]
+    fn opening_book() {
+        use crate::error::BookError;
+        use crate::lookup::OpeningBook;
+        // an empty 7x6 book of depth 0 with a table of 2 entries
+        let bytes = [7, 6, 0, 2, 1, 1, 0, 0, 0, 0, 0, 0];
+        let book = std::sync::Arc::new(OpeningBook::from_bytes(&bytes).unwrap());
+        let mut solver = Solver::default();
+        assert!(solver.opening_book().is_none());
+        solver.set_opening_book(Some(book.clone())).unwrap();
+        assert!(solver.opening_book().is_some());
+        solver.set_opening_book(None).unwrap();
+        assert!(solver.opening_book().is_none());
+        let mut small = super::Solver::<6, 5>::default();
+        assert!(matches!(
+            small.set_opening_book(Some(book)),
+            Err(BookError::WrongSize {
+                expected: (6, 5),
+                found: (7, 6)
+            })
+        ));
+    }
+
+    #[test]
+    fn generate_opening_book() {
+        use crate::lookup::OpeningBook;
+        let mut solver = super::Solver::<5, 4>::default();
+        let book = solver.generate_opening_book(3, 12, |_, _| {});
+        let book = OpeningBook::from_bytes(&book.to_bytes()).unwrap();
+        assert_eq!((book.size(), book.depth()), ((5, 4), 3));
+        for moves in ["", "3", "1", "33", "15", "333", "125"] {
+            let mut p = crate::position::Position::<5, 4>::default();
+            p.apply_str(moves);
+            assert_eq!(book.get(&p), Some(solver.solve(&p, false)), "{moves}");
+        }
+
+        // a solver with the book agrees with one without
+        let mut with_book = super::Solver::<5, 4>::default();
+        with_book
+            .set_opening_book(Some(std::sync::Arc::new(book)))
+            .unwrap();
+        for moves in ["3", "3333", "1254"] {
+            let mut p = crate::position::Position::<5, 4>::default();
+            p.apply_str(moves);
+            assert_eq!(
+                with_book.solve(&p, false),
+                solver.solve(&p, false),
+                "{moves}"
+            );
+        }
+    }
+
+    /// writes a book for `BOOK_SIZE` (7x6, 6x5, 8x7, 8x8 or 9x7, default 7x6) positions up to
+    /// `BOOK_DEPTH` (default 8) moves in a table of `1 << BOOK_LOG_SIZE` (default 21) entries
+    /// to `BOOK_PATH` (default `<size>_<depth>.book`):
+    /// `BOOK_DEPTH=10 cargo test --release make_opening_book -- --ignored --nocapture`
+    #[test]
+    #[ignore = "not a test"]
+    fn make_opening_book() {
+        fn make<const W: usize, const H: usize, B: crate::bitboard::Bitboard>(
+            depth: usize,
+            log_size: usize,
+        ) {
+            let path = std::env::var("BOOK_PATH").unwrap_or(format!("{W}x{H}_{depth}.book"));
+            let mut solver = super::Solver::<W, H, B>::default();
+            let started = std::time::Instant::now();
+            let book = solver.generate_opening_book(depth, log_size, |done, total| {
+                if done % 1000 == 0 || done == total {
+                    eprintln!("{done}/{total} {:?}", started.elapsed());
+                }
+            });
+            book.save_to_path(&path).unwrap();
+            eprintln!("wrote {path}");
+        }
+        let var = |name: &str, default: usize| {
+            std::env::var(name).map_or(default, |v| v.parse().expect(name))
+        };
+        let depth = var("BOOK_DEPTH", 8);
+        let log_size = var("BOOK_LOG_SIZE", 21);
+        match std::env::var("BOOK_SIZE").as_deref().unwrap_or("7x6") {
+            "7x6" => make::<7, 6, u64>(depth, log_size),
+            "6x5" => make::<6, 5, u64>(depth, log_size),
+            "8x7" => make::<8, 7, u64>(depth, log_size),
+            "8x8" => make::<8, 8, u128>(depth, log_size),
+            "9x7" => make::<9, 7, u128>(depth, log_size),
+            size => panic!("unsupported BOOK_SIZE {size}"),
+        }
+    }
+
+    #[test]
+    fn save_table() {
+        use crate::error::TableError;
+        let mut p = Position::default();
+        p.apply_str("52753311433677442422121");
+        let mut solver = Solver::default();
+        let score = solver.solve(&p, false);
+        let nodes = solver.last_stats().nodes;
+        let bytes = solver.table_to_bytes();
+
+        let mut warm = Solver::default();
+        warm.load_table_bytes(&bytes).unwrap();
+        assert_eq!(warm.table_to_bytes(), bytes);
+        assert_eq!(warm.solve(&p, false), score);
+        assert!(warm.last_stats().nodes < nodes);
+
+        let mut shared =
+            super::Solver::<7, 6, u64, std::sync::Arc<crate::lookup::SharedTable<u64>>>::default();
+        shared.solve(&p, false);
+        let shared_bytes = shared.table_to_bytes();
+        let mut shared_warm =
+            super::Solver::<7, 6, u64, std::sync::Arc<crate::lookup::SharedTable<u64>>>::default();
+        shared_warm.load_table_bytes(&shared_bytes).unwrap();
+        assert_eq!(shared_warm.table_to_bytes(), shared_bytes);
+
+        assert!(matches!(
+            warm.load_table_bytes(&shared_bytes),
+            Err(TableError::BadHeader(_))
+        ));
+        assert!(matches!(
+            super::Solver::<6, 5>::default().load_table_bytes(&bytes),
+            Err(TableError::WrongSize { .. })
+        ));
+        assert!(matches!(
+            warm.load_table_bytes(&bytes[..bytes.len() - 1]),
+            Err(TableError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn table_bytes() {
+        let mut small = Solver::with_table_bytes(1 << 20);
+        let mut large = Solver::default();
+        for line in include_str!("Test_L3_R1").lines().take(20) {
+            let moves = line.split_once(' ').unwrap().0;
+            let mut p = Position::default();
+            p.apply_str(moves);
+            assert_eq!(small.solve(&p, false), large.solve(&p, false), "{moves}");
+        }
+
+        let mut p = Position::default();
+        p.apply_str("52753311433677442422121");
+        large.solve(&p, false);
+        large.solve(&p, false);
+        let warm = large.last_stats().nodes;
+        large.clear_table();
+        large.solve(&p, false);
+        assert!(large.last_stats().nodes > warm);
+        large.set_table_bytes(1 << 20);
+        assert_eq!(large.table_to_bytes().len(), small.table_to_bytes().len());
+
+        // 32-bit partial keys and the index tell 49-bit keys apart with at least 1 << 17 entries
+        let tiny = Solver::with_table_bytes(1 << 12);
+        assert!(tiny.table.size() >= 1 << 17);
+        assert!(tiny.table.size() < small.table.size());
+        large.set_table_bytes(0);
+        assert_eq!(large.table.size(), tiny.table.size());
+    }
+
+    #[test]
+    fn analyze() {
+        let mut solver = Solver::default();
+        let mut p = Position::default();
+        p.apply_str("121212");
+        let scores = solver.analyze(&p, false);
+        // column 1 wins now, columns 3 to 7 let the second player win in column 2
+        assert_eq!(scores[0], Some(-18));
+        assert!(scores[2..].iter().all(|&s| s == Some(18)));
+    }
+
+    #[test]
+    fn analyze_parallel() {
+        use crate::lookup::SharedTable;
+        let mut sequential = Solver::default();
+        use std::sync::Arc;
+        let mut parallel = super::Solver::<7, 6, u64, Arc<SharedTable<u64>>>::default();
+        let mut small = Solver::with_table_bytes(1 << 20);
+        let shared: Arc<SharedTable<u64>> = small.parallel_table();
+        for line in include_str!("Test_L2_R1").lines().take(50) {
+            let moves = line.split_once(' ').unwrap().0;
+            let mut p = Position::default();
+            p.apply_str(moves);
+            let expected = sequential.analyze_bounds(&p, false);
+            assert_eq!(parallel.analyze_parallel(&p, false), expected);
+            assert!(!parallel.is_interrupted());
+            let bounds = small.analyze_parallel_with(&shared, &p, false);
+            assert_eq!(bounds, expected);
+            assert!(!small.is_interrupted());
+        }
+    }
+
+    #[test]
+    fn weak() {
+        let mut solver = Solver::default();
+        for line in include_str!("Test_L2_R1").lines().take(50) {
+            let (moves, score) = line.split_once(' ').unwrap();
+            let score = score.parse::<i32>().unwrap();
+            let mut p = Position::default();
+            p.apply_str(moves);
+            let bounds = solver.solve_bounds(&p, true);
+            assert!(bounds.is_exact(), "{moves}");
+            assert_eq!(bounds.score(), score.signum(), "{moves}");
+        }
+    }
+
+    #[test]
+    fn move_order() {
+        assert_eq!(Position::MOVE_ORDER, [3, 2, 4, 1, 5, 0, 6]);
+        assert_eq!(
+            crate::position::Position::<6, 5>::MOVE_ORDER,
+            [3, 2, 4, 1, 5, 0]
+        );
+        assert_eq!(
+            crate::position::Position::<8, 7>::MOVE_ORDER,
+            [4, 3, 5, 2, 6, 1, 7, 0]
+        );
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn random_midgame() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut solver = Solver::default();
+        let mut all_moves = all_moves::<7, 6>();
+        for _ in 0..10 {
+            all_moves.shuffle(rng);
+            let min = Position::AREA / 3;
+            let max = Position::AREA / 3 * 2;
+            let range = min..max;
+            let moves = &all_moves[0..rng.random_range(range)];
+            test_correctness(&mut solver, moves);
+        }
+    }
+
+    #[test]
+    #[ignore = "slow"]
+    fn random_earlygame() {
+        use rand::prelude::*;
+        let rng = &mut rand::rng();
+        let mut solver = Solver::default();
+        let mut all_moves = all_moves::<7, 6>();
+        for _ in 0..1 {
+            all_moves.shuffle(rng);
+            let min = Position::AREA / 6;
+            let max = Position::AREA / 3;
+            let range = min..max;
+            let moves = &all_moves[0..rng.random_range(range)];
+            let mut p = Position::default();
+            let code = moves
+                .iter()
+                .copied()
+                .map(|c| char::from_digit(c as u32, 10).unwrap())
+                .collect::<String>();
+            p.apply_moves(moves.iter().copied());
+            eprint!("{code} ");
+            let answer = solver.solve(&p, false);
+            eprintln!("{answer}");
+        }
+    }
+
+    fn test_against_data(data: &str) {
+        let mut solver = Solver::default();
+        for line in data.lines() {
+            let (moves, score) = line.split_once(' ').unwrap();
+            let score = score.parse::<i32>().unwrap();
+            let mut p = Position::default();
+            p.apply_str(moves);
+            let solved_score = solver.solve(&p, false);
+            eprintln!("{moves}");
+            assert_eq!(score, solved_score);
+        }
+    }
+
+    #[test]
+    fn data_l1r1() {
+        test_against_data(include_str!("Test_L1_R1"));
+    }
+    #[test]
+    #[ignore = "slow"]
+    fn data_l1r2() {
+        test_against_data(include_str!("Test_L1_R2"));
+    }
+    #[test]
+    #[ignore = "slow"]
+    fn data_l1r3() {
+        test_against_data(include_str!("Test_L1_R3"));
+    }
+    #[test]
+    fn data_l2r1() {
+        test_against_data(include_str!("Test_L2_R1"));
+    }
+    #[test]
+    #[ignore = "slow"]
+    fn data_l2r2() {
+        test_against_data(include_str!("Test_L2_R2"));
+    }
+    #[test]
+    fn data_l3r1() {
+        test_against_data(include_str!("Test_L3_R1"));
+    }
+
+    #[test]
+    #[ignore = "not a test"]
+    fn data_strip() {
+        let s = include_str!("Test_L1_R2");
+        for line in s.lines() {
+            let moves = line.split_once(' ').unwrap().0;
+            eprintln!("{moves}");
+        }
+    }
+}
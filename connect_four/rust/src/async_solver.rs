@@ -0,0 +1,296 @@
+use godot::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex, mpsc};
+use std::thread::JoinHandle;
+
+use crate::{AnalyzedMove, AnySolver, MB, MoveAnalysis, read_opening_book};
+use c4solver_core::error::C4Error;
+use c4solver_core::lookup::OpeningBook;
+
+struct Request {
+    generation: u64,
+    moves: Vec<u8>,
+    weak: bool,
+    parallel: bool,
+    size: (usize, usize),
+    book: Option<Arc<OpeningBook>>,
+    table_bytes: Option<usize>,
+    /// empty the table before analyzing
+    clear_table: bool,
+}
+
+struct Response {
+    generation: u64,
+    moves: Vec<u8>,
+    result: Result<Vec<Option<MoveAnalysis>>, C4Error>,
+}
+
+#[derive(Default)]
+struct Pending {
+    /// only the latest request is kept, older ones are stale
+    request: Option<Request>,
+    quit: bool,
+}
+
+#[derive(Default)]
+struct Shared {
+    pending: Mutex<Pending>,
+    wake: Condvar,
+    /// interrupts the analysis in progress, set when it becomes stale
+    stop: Arc<AtomicBool>,
+}
+
+/// owns the thread running the solver, results are polled from the main thread
+struct Worker {
+    shared: Arc<Shared>,
+    results: mpsc::Receiver<Response>,
+    thread: Option<JoinHandle<()>>,
+}
+impl Worker {
+    fn spawn() -> Self {
+        let shared = Arc::new(Shared::default());
+        let (sender, results) = mpsc::channel();
+        let thread = {
+            let shared = shared.clone();
+            std::thread::spawn(move || work(&shared, &sender))
+        };
+        Self {
+            shared,
+            results,
+            thread: Some(thread),
+        }
+    }
+
+    fn request(&self, request: Request) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        pending.request = Some(request);
+        self.shared.stop.store(true, Ordering::Relaxed);
+        self.shared.wake.notify_one();
+    }
+
+    fn cancel(&self) {
+        let mut pending = self.shared.pending.lock().unwrap();
+        pending.request = None;
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn shutdown(&mut self) {
+        {
+            let mut pending = self.shared.pending.lock().unwrap();
+            pending.quit = true;
+            self.shared.stop.store(true, Ordering::Relaxed);
+            self.shared.wake.notify_one();
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn work(shared: &Shared, results: &mpsc::Sender<Response>) {
+    // created on the first request
+    let mut cached: Option<AnySolver> = None;
+    let mut table_bytes = None;
+    loop {
+        let request = {
+            let mut pending = shared.pending.lock().unwrap();
+            loop {
+                if pending.quit {
+                    return;
+                }
+                if let Some(request) = pending.request.take() {
+                    // cleared under the lock so a newer request can't be missed
+                    shared.stop.store(false, Ordering::Relaxed);
+                    break request;
+                }
+                pending = shared.wake.wait(pending).unwrap();
+            }
+        };
+        let solver = match cached.take() {
+            Some(mut solver)
+                if solver.size() == request.size && table_bytes == request.table_bytes =>
+            {
+                if request.clear_table {
+                    solver.clear_table();
+                }
+                cached.insert(solver)
+            }
+            old => {
+                // frees the old table before allocating the new one
+                drop(old);
+                let (width, height) = request.size;
+                let Some(new) = AnySolver::new(width, height, request.table_bytes) else {
+                    continue;
+                };
+                table_bytes = request.table_bytes;
+                cached.insert(new)
+            }
+        };
+        solver.set_stop_flag(shared.stop.clone());
+        // fails for a book of another size, the search then runs without it
+        if solver.set_opening_book(request.book).is_err() {
+            let _ = solver.set_opening_book(None);
+        }
+        let result = solver.analyze(&request.moves, request.weak, request.parallel);
+        if shared.stop.load(Ordering::Relaxed) {
+            // superseded, cancelled or quitting
+            continue;
+        }
+        let response = Response {
+            generation: request.generation,
+            moves: request.moves,
+            result,
+        };
+        if results.send(response).is_err() {
+            return;
+        }
+    }
+}
+
+/// runs `C4Solver.analyze` on its own thread.
+/// only the latest request is answered, older ones still pending or running are dropped.
+#[derive(GodotClass)]
+#[class(init, base = Node)]
+struct C4AsyncSolver {
+    worker: Option<Worker>,
+    #[init(val = (7, 6))]
+    size: (usize, usize),
+    book: Option<Arc<OpeningBook>>,
+    table_bytes: Option<usize>,
+    /// `clear_table` was called since the last request
+    clear_table: bool,
+    /// identifies the latest request, responses to older ones are dropped
+    generation: u64,
+    base: Base<Node>,
+}
+
+#[godot_api]
+impl INode for C4AsyncSolver {
+    fn enter_tree(&mut self) {
+        if self.worker.is_none() {
+            self.worker = Some(Worker::spawn());
+        }
+    }
+
+    fn exit_tree(&mut self) {
+        if let Some(mut worker) = self.worker.take() {
+            worker.shutdown();
+        }
+    }
+
+    fn process(&mut self, _delta: f64) {
+        let Some(worker) = &self.worker else {
+            return;
+        };
+        let responses: Vec<Response> = worker.results.try_iter().collect();
+        for response in responses {
+            if response.generation != self.generation {
+                continue;
+            }
+            let moves = PackedByteArray::from(response.moves.as_slice());
+            match response.result {
+                Ok(analyses) => {
+                    let analyses = MoveAnalysis::to_godot(&analyses);
+                    self.signals().analysis_ready().emit(&moves, &analyses);
+                }
+                Err(e) => godot_error!("invalid moves {moves}: {e}"),
+            }
+        }
+    }
+}
+
+#[godot_api]
+impl C4AsyncSolver {
+    /// emitted on the main thread with the `moves` of the request and the result of
+    /// `C4Solver.analyze`
+    #[signal]
+    fn analysis_ready(moves: PackedByteArray, analyses: Array<Option<Gd<AnalyzedMove>>>);
+
+    /// see `C4Solver.set_board_size`, applies to later requests
+    #[func]
+    fn set_board_size(&mut self, width: u32, height: u32) -> bool {
+        let size = (width as usize, height as usize);
+        if AnySolver::supports(size.0, size.1) {
+            self.size = size;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// see `C4Solver.load_opening_book`, applies to later requests
+    #[func]
+    fn load_opening_book(&mut self, path: GString) -> bool {
+        let Some(book) = read_opening_book(&path) else {
+            return false;
+        };
+        if book.size() != self.size {
+            let (w, h) = book.size();
+            godot_error!("opening book {path} is for a {w}x{h} board");
+            return false;
+        }
+        self.book = Some(book);
+        true
+    }
+
+    #[func]
+    fn clear_opening_book(&mut self) {
+        self.book = None;
+    }
+
+    /// see `C4Solver.set_table_size_mb`, applies to later requests
+    #[func]
+    fn set_table_size_mb(&mut self, mb: i64) -> bool {
+        if mb <= 0 {
+            godot_error!("invalid table size {mb} MB");
+            return false;
+        }
+        self.table_bytes = Some(mb as usize * MB);
+        true
+    }
+
+    /// see `C4Solver.clear_table`, applies to the next request
+    #[func]
+    fn clear_table(&mut self) {
+        self.clear_table = true;
+    }
+
+    /// replaces any request not answered yet
+    #[func]
+    fn request_analysis(
+        &mut self,
+        moves: PackedByteArray,
+        #[opt(default = true)] weak: bool,
+        #[opt(default = false)] parallel: bool,
+    ) {
+        self.generation += 1;
+        let request = Request {
+            generation: self.generation,
+            moves: moves.to_vec(),
+            weak,
+            parallel,
+            size: self.size,
+            book: self.book.clone(),
+            table_bytes: self.table_bytes,
+            clear_table: std::mem::take(&mut self.clear_table),
+        };
+        match &self.worker {
+            Some(worker) => worker.request(request),
+            None => godot_error!("C4AsyncSolver must be in the scene tree to analyze"),
+        }
+    }
+
+    /// drops the pending request, `analysis_ready` won't be emitted for it
+    #[func]
+    fn cancel(&mut self) {
+        self.generation += 1;
+        if let Some(worker) = &self.worker {
+            worker.cancel();
+        }
+    }
+}
@@ -1,10 +1,23 @@
+use godot::classes::FileAccess;
+use godot::classes::file_access::ModeFlags;
 use godot::prelude::*;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-pub mod lookup;
-pub mod position;
-pub mod solver;
+mod async_solver;
 
-use position::Position;
+use c4solver_core::bitboard::Bitboard;
+use c4solver_core::error::{BookError, C4Error, TableError};
+use c4solver_core::game::Game;
+use c4solver_core::lookup::{OpeningBook, SharedTable};
+use c4solver_core::outcome::{GameResult, MoveReason, Outcome};
+use c4solver_core::player::{Player, Strength};
+use c4solver_core::position::{Position, Threats};
+use c4solver_core::puzzle::Puzzle;
+use c4solver_core::solver::{MoveClass, MoveReview, ScoreBounds, Solver};
+
+const MB: usize = 1 << 20;
 
 #[derive(GodotClass)]
 #[class(init)]
@@ -12,7 +25,8 @@ struct AnalyzedMove {
     /// column to play
     #[var]
     col: u32,
-    /// score of the move, the more positive the better (more likely to win)
+    /// score of the move, the more positive the better (more likely to win), winning moves
+    /// included
     #[var]
     score: i32,
     /// true if playing this move wins immediately
@@ -24,20 +38,454 @@ struct AnalyzedMove {
     /// true if NOT playing this move loses immediately
     #[var]
     forced: bool,
+    /// false if the search was interrupted and `score` is only a guess
+    #[var]
+    exact: bool,
+    /// `WIN`, `DRAW` or `LOSS` for the player making the move, if both sides play perfectly.
+    /// only a guess, like `score`, if not `exact`
+    #[var]
+    outcome: i64,
+    /// plies until the game ends, this move included.
+    /// -1 if unknown: weak or interrupted searches don't tell
+    #[var]
+    plies_to_end: i32,
+    /// 0 if the first player wins, 1 if the second does, -1 for a draw or if `plies_to_end` is
+    /// unknown
+    #[var]
+    winner: i32,
+    /// why to play (or not) this move in a few words, e.g. "blocks a threat" or "wins in 3 moves"
+    #[var]
+    reason: GString,
+}
+
+#[godot_api]
+impl AnalyzedMove {
+    #[constant]
+    const WIN: i64 = 1;
+    #[constant]
+    const DRAW: i64 = 0;
+    #[constant]
+    const LOSS: i64 = -1;
 }
 
 impl AnalyzedMove {
-    fn new(position: &Position, col: usize, score: i32) -> Self {
-        let winning = position.is_winning_move(col);
-        let losing = position.played(col).can_win_next();
-        let forced = position.is_forced_move(col);
+    fn new(m: &MoveAnalysis) -> Self {
+        Self {
+            col: m.col as u32,
+            score: -m.bounds.score(),
+            winning: m.winning,
+            losing: m.losing,
+            forced: m.forced,
+            exact: m.bounds.is_exact(),
+            outcome: match m.result {
+                GameResult::Win => Self::WIN,
+                GameResult::Draw => Self::DRAW,
+                GameResult::Loss => Self::LOSS,
+            },
+            plies_to_end: m.outcome.map_or(-1, |o| o.plies as i32),
+            winner: m.outcome.and_then(|o| o.winner).map_or(-1, |w| w as i32),
+            reason: m.reason.to_string().into(),
+        }
+    }
+}
+
+/// a move of a game reviewed by `C4Solver.review_game`,
+/// scores are from the point of view of the player who moved
+#[derive(GodotClass)]
+#[class(init)]
+struct ReviewedMove {
+    #[var]
+    col: u32,
+    #[var]
+    score: i32,
+    /// score of the best moves of the position
+    #[var]
+    best_score: i32,
+    /// every column scoring `best_score`
+    #[var]
+    best_moves: PackedByteArray,
+    /// one of `BEST`, `INACCURACY`, `BLUNDER` or `MISSED_WIN`
+    #[var]
+    classification: i64,
+    /// false if a search was interrupted and the scores are only guesses
+    #[var]
+    exact: bool,
+}
+
+#[godot_api]
+impl ReviewedMove {
+    /// scores as well as the best move
+    #[constant]
+    const BEST: i64 = 0;
+    /// keeps the result (win, draw or loss) but wins slower or loses faster
+    #[constant]
+    const INACCURACY: i64 = 1;
+    /// turns a draw into a loss
+    #[constant]
+    const BLUNDER: i64 = 2;
+    /// turns a win into a draw or a loss
+    #[constant]
+    const MISSED_WIN: i64 = 3;
+}
+
+impl ReviewedMove {
+    fn new(review: &MoveReview) -> Self {
         Self {
-            col: col as u32,
-            score,
-            winning,
-            losing,
-            forced,
+            col: review.col as u32,
+            score: review.score,
+            best_score: review.best_score,
+            best_moves: review.best_moves.iter().map(|&col| col as u8).collect(),
+            classification: match review.class {
+                MoveClass::Best => Self::BEST,
+                MoveClass::Inaccuracy => Self::INACCURACY,
+                MoveClass::Blunder => Self::BLUNDER,
+                MoveClass::MissedWin => Self::MISSED_WIN,
+            },
+            exact: review.exact,
+        }
+    }
+}
+
+/// the content of an `AnalyzedMove`, without the Godot object so it can be sent across threads
+#[derive(Clone, Copy)]
+struct MoveAnalysis {
+    col: usize,
+    /// bounds of the opponent's score after the move
+    bounds: ScoreBounds,
+    winning: bool,
+    losing: bool,
+    forced: bool,
+    /// for the player making the move, a guess if the bounds aren't exact
+    result: GameResult,
+    /// for the player making the move, None if the search was weak or interrupted
+    outcome: Option<Outcome>,
+    reason: MoveReason,
+}
+impl MoveAnalysis {
+    /// one entry per column, None if the column is full
+    fn analyze<const W: usize, const H: usize, B: Bitboard>(
+        solver: &mut Solver<W, H, B>,
+        parallel_table: &mut ParallelTable<B>,
+        position: &Position<W, H, B>,
+        weak: bool,
+        parallel: bool,
+    ) -> Vec<Option<Self>> {
+        let bounds = if parallel {
+            // the solver's own table isn't thread safe
+            let table = parallel_table.get_or_insert_with(|| solver.parallel_table());
+            solver.analyze_parallel_with(table, position, weak)
+        } else {
+            solver.analyze_bounds(position, weak)
+        };
+        bounds
+            .into_iter()
+            .enumerate()
+            .map(|(col, bounds)| {
+                bounds.map(|bounds| {
+                    let score = -bounds.score();
+                    // only exact strong scores tell how far the end is
+                    let distance_known = bounds.is_exact() && !weak;
+                    let outcome = Outcome::new(position, score);
+                    Self {
+                        col,
+                        bounds,
+                        winning: position.is_winning_move(col),
+                        losing: position.played(col).can_win_next(),
+                        forced: position.is_forced_move(col),
+                        result: outcome.result,
+                        outcome: distance_known.then_some(outcome),
+                        reason: MoveReason::new(position, col, score, !distance_known),
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn to_godot(analyses: &[Option<Self>]) -> Array<Option<Gd<AnalyzedMove>>> {
+        analyses
+            .iter()
+            .map(|m| m.as_ref().map(|m| Gd::from_object(AnalyzedMove::new(m))))
+            .collect()
+    }
+}
+
+/// table shared by the threads of parallel analyses, created on the first one
+type ParallelTable<B> = Option<Arc<SharedTable<B>>>;
+
+/// defines `AnySolver`, `AnyGame` and the macros matching on them from the one list of
+/// supported board sizes below, each given as `variant: width x height, bitboard`.
+/// `$d` is a `$` for the metavariables of the macros it defines.
+macro_rules! board_sizes {
+    ($d:tt $($variant:ident: $width:literal x $height:literal, $bitboard:ty;)*) => {
+        /// board sizes supported by `C4Solver`; each variant owns its own transposition tables
+        enum AnySolver {
+            $($variant(Solver<$width, $height, $bitboard>, ParallelTable<$bitboard>),)*
+        }
+        impl AnySolver {
+            fn supports(width: usize, height: usize) -> bool {
+                matches!((width, height), $(($width, $height))|*)
+            }
+            /// `table_bytes` limits the size of the transposition table,
+            /// see `Solver::with_table_bytes`
+            fn new(width: usize, height: usize, table_bytes: Option<usize>) -> Option<Self> {
+                fn solver<const W: usize, const H: usize, B: Bitboard>(
+                    table_bytes: Option<usize>,
+                ) -> Solver<W, H, B> {
+                    table_bytes.map_or_else(Solver::default, Solver::with_table_bytes)
+                }
+                match (width, height) {
+                    $(($width, $height) => Some(Self::$variant(solver(table_bytes), None)),)*
+                    _ => None,
+                }
+            }
+            fn size(&self) -> (usize, usize) {
+                match self {
+                    $(Self::$variant(..) => ($width, $height),)*
+                }
+            }
+        }
+
+        /// evaluates `$body` with `$s` bound to the concrete `Solver<W, H, B>` inside
+        /// `$solver`, and `$t` to its `ParallelTable<B>` if given
+        macro_rules! with_solver {
+            ($d solver:expr, $d s:ident => $d body:expr) => {
+                match $d solver {
+                    $(AnySolver::$variant($d s, _) => $d body,)*
+                }
+            };
+            ($d solver:expr, ($d s:ident, $d t:ident) => $d body:expr) => {
+                match $d solver {
+                    $(AnySolver::$variant($d s, $d t) => $d body,)*
+                }
+            };
+        }
+
+        /// `AnySolver` counterpart for games
+        enum AnyGame {
+            $($variant(Game<$width, $height, $bitboard>),)*
+        }
+        impl AnyGame {
+            fn new(width: usize, height: usize) -> Option<Self> {
+                match (width, height) {
+                    $(($width, $height) => Some(Self::$variant(Game::default())),)*
+                    _ => None,
+                }
+            }
+            fn size(&self) -> (usize, usize) {
+                match self {
+                    $(Self::$variant(_) => ($width, $height),)*
+                }
+            }
+        }
+
+        /// evaluates `$body` with `$g` bound to the concrete `Game<W, H, B>` inside `$game`
+        macro_rules! with_game {
+            ($d game:expr, $d g:ident => $d body:expr) => {
+                match $d game {
+                    $(AnyGame::$variant($d g) => $d body,)*
+                }
+            };
+        }
+
+        /// evaluates `$body` with `$s` and `$g` bound to a solver and game of the same
+        /// board size, panics if the sizes differ. `$t` is bound to the `ParallelTable<B>` of
+        /// the solver if given.
+        macro_rules! with_solver_and_game {
+            ($d solver:expr, $d game:expr, ($d s:ident, $d g:ident) => $d body:expr) => {
+                match ($d solver, $d game) {
+                    $((AnySolver::$variant($d s, _), AnyGame::$variant($d g)) => $d body,)*
+                    _ => unreachable!("solver and game board sizes differ"),
+                }
+            };
+            (
+                $d solver:expr,
+                $d game:expr,
+                ($d s:ident, $d t:ident, $d g:ident) => $d body:expr
+            ) => {
+                match ($d solver, $d game) {
+                    $((AnySolver::$variant($d s, $d t), AnyGame::$variant($d g)) => $d body,)*
+                    _ => unreachable!("solver and game board sizes differ"),
+                }
+            };
+        }
+
+        /// evaluates `$body` with `$w`, `$h` and `$b` defined as the width, height and
+        /// bitboard type of the board of `$size`, which must pass `AnySolver::supports`
+        macro_rules! with_board_size {
+            ($d size:expr, ($d w:ident, $d h:ident, $d b:ident) => $d body:expr) => {
+                match $d size {
+                    $(($width, $height) => {
+                        const $d w: usize = $width;
+                        const $d h: usize = $height;
+                        type $d b = $bitboard;
+                        $d body
+                    })*
+                    (w, h) => unreachable!("unsupported board size {w}x{h}"),
+                }
+            };
+        }
+    };
+}
+
+board_sizes! {$
+    S7x6: 7 x 6, u64;
+    S6x5: 6 x 5, u64;
+    S8x7: 8 x 7, u64;
+    S8x8: 8 x 8, u128;
+    S9x7: 9 x 7, u128;
+}
+
+impl Default for AnyGame {
+    fn default() -> Self {
+        Self::S7x6(Game::default())
+    }
+}
+
+impl AnySolver {
+    fn set_stop_flag(&mut self, stop: Arc<AtomicBool>) {
+        with_solver!(self, s => s.set_stop_flag(stop));
+    }
+
+    /// empties the parallel table too
+    fn clear_table(&mut self) {
+        with_solver!(self, (s, t) => {
+            s.clear_table();
+            if let Some(table) = t {
+                table.clear();
+            }
+        });
+    }
+
+    fn set_opening_book(&mut self, book: Option<Arc<OpeningBook>>) -> Result<(), BookError> {
+        with_solver!(self, s => s.set_opening_book(book))
+    }
+
+    fn table_to_bytes(&self) -> Vec<u8> {
+        with_solver!(self, s => s.table_to_bytes())
+    }
+
+    fn load_table_bytes(&mut self, bytes: &[u8]) -> Result<(), TableError> {
+        with_solver!(self, s => s.load_table_bytes(bytes))
+    }
+
+    /// `MoveAnalysis::analyze` for a move list, doesn't report errors to Godot
+    fn analyze(
+        &mut self,
+        moves: &[u8],
+        weak: bool,
+        parallel: bool,
+    ) -> Result<Vec<Option<MoveAnalysis>>, C4Error> {
+        with_solver!(self, (s, t) => {
+            let mut position = Position::default();
+            position.try_apply_moves(moves.iter().map(|b| *b as usize))?;
+            position.check_not_over()?;
+            Ok(MoveAnalysis::analyze(s, t, &position, weak, parallel))
+        })
+    }
+}
+
+/// reads a `.book` file through Godot so `res://` and `user://` paths work,
+/// reporting errors with `godot_error!`
+fn read_opening_book(path: &GString) -> Option<Arc<OpeningBook>> {
+    let bytes = FileAccess::get_file_as_bytes(path);
+    if bytes.is_empty() {
+        godot_error!(
+            "cannot read opening book {path}: {:?}",
+            FileAccess::get_open_error()
+        );
+        return None;
+    }
+    match OpeningBook::from_bytes(bytes.as_slice()) {
+        Ok(book) => Some(Arc::new(book)),
+        Err(e) => {
+            godot_error!("invalid opening book {path}: {e}");
+            None
+        }
+    }
+}
+
+/// `Position::threats` as a Dictionary: `threats` is an array of dictionaries with the
+/// col, row, player and odd (odd row counting from 1 at the bottom) of each threat,
+/// `zugzwang` is the player controlling zugzwang or -1 if unknown
+fn threats_to_godot(threats: &Threats) -> Dictionary {
+    let cells: Array<Dictionary> = threats
+        .threats
+        .iter()
+        .map(|t| {
+            let mut dict = Dictionary::new();
+            dict.set("col", t.col as i64);
+            dict.set("row", t.row as i64);
+            dict.set("player", t.player as i64);
+            dict.set("odd", t.is_odd());
+            dict
+        })
+        .collect();
+    let mut dict = Dictionary::new();
+    dict.set("threats", cells);
+    dict.set("zugzwang", threats.zugzwang.map_or(-1, |p| p as i64));
+    dict
+}
+
+/// builds a position from a GDScript move list, reporting invalid moves with `godot_error!`
+fn position_from_moves<const W: usize, const H: usize, B: Bitboard>(
+    moves: &PackedByteArray,
+) -> Option<Position<W, H, B>> {
+    let mut position = Position::default();
+    match position.try_apply_moves(moves.as_slice().iter().map(|b| *b as usize)) {
+        Ok(()) => Some(position),
+        Err(e) => {
+            godot_error!("invalid moves {moves}: {e}");
+            None
+        }
+    }
+}
+
+/// `position_from_moves` for the board size of `_solver`
+fn position_for<const W: usize, const H: usize, B: Bitboard>(
+    _solver: &Solver<W, H, B>,
+    moves: &PackedByteArray,
+) -> Option<Position<W, H, B>> {
+    position_from_moves(moves)
+}
+
+/// `position_from_moves` for a position to search, also reporting finished games
+fn position_to_search<const W: usize, const H: usize, B: Bitboard>(
+    moves: &PackedByteArray,
+) -> Option<Position<W, H, B>> {
+    let position = position_from_moves::<W, H, B>(moves)?;
+    match position.check_not_over() {
+        Ok(()) => Some(position),
+        Err(e) => {
+            godot_error!("cannot search {moves}: {e}");
+            None
+        }
+    }
+}
+
+fn solve_moves<const W: usize, const H: usize, B: Bitboard>(
+    solver: &mut Solver<W, H, B>,
+    moves: &PackedByteArray,
+    weak: bool,
+) -> i32 {
+    match position_to_search::<W, H, B>(moves) {
+        Some(p) => solver.solve(&p, weak),
+        None => C4Solver::INVALID_SCORE,
+    }
+}
+
+fn analyze_moves<const W: usize, const H: usize, B: Bitboard>(
+    solver: &mut Solver<W, H, B>,
+    parallel_table: &mut ParallelTable<B>,
+    moves: &PackedByteArray,
+    weak: bool,
+    parallel: bool,
+) -> Array<Option<Gd<AnalyzedMove>>> {
+    match position_to_search::<W, H, B>(moves) {
+        Some(p) => {
+            let analyses = MoveAnalysis::analyze(solver, parallel_table, &p, weak, parallel);
+            MoveAnalysis::to_godot(&analyses)
         }
+        None => Array::new(),
     }
 }
 
@@ -46,33 +494,650 @@ impl AnalyzedMove {
 #[derive(GodotClass)]
 #[class(init)]
 struct C4Solver {
-    solver: solver::Solver,
+    /// created on first use, see `solver`
+    solver: Option<AnySolver>,
+    #[init(val = (7, 6))]
+    size: (usize, usize),
+    /// given to every solver, so stop tokens survive board size changes
+    stop: Arc<AtomicBool>,
+    time_limit: Option<Duration>,
+    node_limit: Option<u64>,
+    /// kept across board size changes, only used on its own size
+    book: Option<Arc<OpeningBook>>,
+    /// None for the default table size
+    table_bytes: Option<usize>,
+}
+impl C4Solver {
+    /// the solver for the current board size, allocating its transposition table on first use
+    fn solver(&mut self) -> &mut AnySolver {
+        self.solver.get_or_insert_with(|| {
+            let (width, height) = self.size;
+            let mut solver = AnySolver::new(width, height, self.table_bytes)
+                .expect("board size checked by set_board_size");
+            let (time_limit, node_limit) = (self.time_limit, self.node_limit);
+            with_solver!(&mut solver, s => {
+                s.set_stop_flag(self.stop.clone());
+                s.set_time_limit(time_limit);
+                s.set_node_limit(node_limit);
+            });
+            // fails for a book of another size, which stays unused
+            let _ = solver.set_opening_book(self.book.clone());
+            solver
+        })
+    }
 }
 
 #[godot_api]
 impl C4Solver {
+    /// returned by `solve` when the move list is invalid or the game is over
+    #[constant]
+    const INVALID_SCORE: i32 = i32::MIN;
+
+    /// switch to a `width`x`height` board, one of 7x6 (default), 6x5, 8x7, 8x8 or 9x7.
+    /// returns false and keeps the current board if the size is not supported.
+    #[func]
+    fn set_board_size(&mut self, width: u32, height: u32) -> bool {
+        let size = (width as usize, height as usize);
+        if !AnySolver::supports(size.0, size.1) {
+            return false;
+        }
+        if self.size != size {
+            // frees the old table now, the new one is allocated on first use
+            self.solver = None;
+            self.size = size;
+        }
+        true
+    }
+
+    /// use the `.book` file at `path` (`res://`, `user://` or a file system path) made for the
+    /// current board size. returns false and keeps the current book on error.
+    #[func]
+    fn load_opening_book(&mut self, path: GString) -> bool {
+        let Some(book) = read_opening_book(&path) else {
+            return false;
+        };
+        if book.size() != self.size {
+            let (w, h) = book.size();
+            godot_error!("opening book {path} is for a {w}x{h} board");
+            return false;
+        }
+        if let Some(solver) = &mut self.solver {
+            let _ = solver.set_opening_book(Some(book.clone()));
+        }
+        self.book = Some(book);
+        true
+    }
+
+    /// search without an opening book, the default
+    #[func]
+    fn clear_opening_book(&mut self) {
+        self.book = None;
+        if let Some(solver) = &mut self.solver {
+            let _ = solver.set_opening_book(None);
+        }
+    }
+
+    #[func]
+    fn has_opening_book(&self) -> bool {
+        self.book
+            .as_ref()
+            .is_some_and(|book| book.size() == self.size)
+    }
+
+    /// empties the transposition table and limits it to about `mb` megabytes,
+    /// e.g. to save memory on the web. returns false if `mb` is not positive.
+    #[func]
+    fn set_table_size_mb(&mut self, mb: i64) -> bool {
+        if mb <= 0 {
+            godot_error!("invalid table size {mb} MB");
+            return false;
+        }
+        let bytes = mb as usize * MB;
+        self.table_bytes = Some(bytes);
+        // emptied anyway, the new table is allocated on first use
+        self.solver = None;
+        true
+    }
+
+    /// forgets every position searched so far, e.g. between games
+    #[func]
+    fn clear_table(&mut self) {
+        if let Some(solver) = &mut self.solver {
+            solver.clear_table();
+        }
+    }
+
+    /// saves the transposition table to `path` (`res://`, `user://` or a file system path),
+    /// returns false on error
+    #[func]
+    fn save_table(&mut self, path: GString) -> bool {
+        let Some(mut file) = FileAccess::open(&path, ModeFlags::WRITE) else {
+            godot_error!(
+                "cannot write table {path}: {:?}",
+                FileAccess::get_open_error()
+            );
+            return false;
+        };
+        file.store_buffer(&PackedByteArray::from(
+            self.solver().table_to_bytes().as_slice(),
+        ));
+        file.close();
+        true
+    }
+
+    /// replaces the transposition table with one saved by `save_table` for the same board size.
+    /// returns false and keeps the current table on error.
+    #[func]
+    fn load_table(&mut self, path: GString) -> bool {
+        let bytes = FileAccess::get_file_as_bytes(&path);
+        if bytes.is_empty() {
+            godot_error!(
+                "cannot read table {path}: {:?}",
+                FileAccess::get_open_error()
+            );
+            return false;
+        }
+        match self.solver().load_table_bytes(bytes.as_slice()) {
+            Ok(()) => true,
+            Err(e) => {
+                godot_error!("cannot load table {path}: {e}");
+                false
+            }
+        }
+    }
+
+    /// interrupt `solve`/`analyze` after `ms` milliseconds, no limit if `ms` <= 0
+    #[func]
+    fn set_time_limit_ms(&mut self, ms: i64) {
+        self.time_limit = (ms > 0).then(|| Duration::from_millis(ms as u64));
+        let limit = self.time_limit;
+        if let Some(solver) = &mut self.solver {
+            with_solver!(solver, s => s.set_time_limit(limit));
+        }
+    }
+
+    /// interrupt `solve`/`analyze` after visiting `nodes` positions, no limit if `nodes` <= 0
+    #[func]
+    fn set_node_limit(&mut self, nodes: i64) {
+        self.node_limit = (nodes > 0).then_some(nodes as u64);
+        let limit = self.node_limit;
+        if let Some(solver) = &mut self.solver {
+            with_solver!(solver, s => s.set_node_limit(limit));
+        }
+    }
+
+    /// true if the last `solve`/`analyze` was cut short, its scores are then only guesses
+    #[func]
+    fn is_interrupted(&self) -> bool {
+        self.solver
+            .as_ref()
+            .is_some_and(|solver| with_solver!(solver, s => s.is_interrupted()))
+    }
+
+    /// counters of the last `solve`, `analyze` or `principal_variation` call:
+    /// nodes, table_hits, table_misses, table_overwrites, book_hits, elapsed_usec, max_depth
+    #[func]
+    fn get_last_stats(&self) -> Dictionary {
+        let stats = self
+            .solver
+            .as_ref()
+            .map(|solver| with_solver!(solver, s => s.last_stats()))
+            .unwrap_or_default();
+        let mut dict = Dictionary::new();
+        dict.set("nodes", stats.nodes as i64);
+        dict.set("table_hits", stats.table_hits as i64);
+        dict.set("table_misses", stats.table_misses as i64);
+        dict.set("table_overwrites", stats.table_overwrites as i64);
+        dict.set("book_hits", stats.book_hits as i64);
+        dict.set("elapsed_usec", stats.elapsed.as_micros() as i64);
+        dict.set("max_depth", stats.max_depth as i64);
+        dict
+    }
+
+    /// token to interrupt this solver from another thread, it stays valid across `set_board_size`
+    #[func]
+    fn get_stop_token(&self) -> Gd<C4StopToken> {
+        Gd::from_object(C4StopToken {
+            stop: self.stop.clone(),
+        })
+    }
+
+    #[func]
+    fn get_board_size(&self) -> Vector2i {
+        let (width, height) = self.size;
+        Vector2i::new(width as i32, height as i32)
+    }
+
+    /// 0 if the first player connected four, 1 if the second did, -1 otherwise
+    #[func]
+    fn winner(&self, moves: PackedByteArray) -> i32 {
+        with_board_size!(self.size, (W, H, B) => position_from_moves::<W, H, B>(&moves)
+            .and_then(|p| p.winner())
+            .map_or(-1, |w| w as i32))
+    }
+
+    /// true if the board is full and nobody connected four
+    #[func]
+    fn is_draw(&self, moves: PackedByteArray) -> bool {
+        with_board_size!(self.size, (W, H, B) => {
+            position_from_moves::<W, H, B>(&moves).is_some_and(|p| p.is_draw())
+        })
+    }
+
+    /// (col, row) of every cell in a completed line, row 0 being the bottom
+    #[func]
+    fn winning_cells(&self, moves: PackedByteArray) -> Array<Vector2i> {
+        with_board_size!(self.size, (W, H, B) => position_from_moves::<W, H, B>(&moves)
+            .map(|p| p.winning_cells())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(col, row)| Vector2i::new(col as i32, row as i32))
+            .collect())
+    }
+
+    /// empty cells that would connect four for either player and who controls zugzwang,
+    /// see `threats_to_godot`. empty if `moves` is invalid.
+    #[func]
+    fn threats(&self, moves: PackedByteArray) -> Dictionary {
+        with_board_size!(self.size, (W, H, B) => position_from_moves::<W, H, B>(&moves)
+            .map(|p| threats_to_godot(&p.threats()))
+            .unwrap_or_default())
+    }
+
+    /// returns `INVALID_SCORE` if `moves` is not a valid sequence or ends the game
     #[func]
     fn solve(&mut self, moves: PackedByteArray, #[opt(default = true)] weak: bool) -> i32 {
-        let mut position = Position::default();
-        position.apply_moves(moves.as_slice().iter().map(|b| *b as usize));
-        self.solver.solve(&position, weak)
+        with_solver!(self.solver(), s => solve_moves(s, &moves, weak))
     }
 
+    /// returns an empty array if `moves` is not a valid sequence or ends the game.
+    /// `parallel` solves every column on its own thread. the threads share a second table as large
+    /// as the solver's (see `set_table_size_mb`), allocated by the first parallel analysis.
     #[func]
     fn analyze(
         &mut self,
         moves: PackedByteArray,
         #[opt(default = true)] weak: bool,
+        #[opt(default = false)] parallel: bool,
     ) -> Array<Option<Gd<AnalyzedMove>>> {
-        let mut p = Position::default();
-        p.apply_moves(moves.as_slice().iter().map(|b| *b as usize));
-        self.solver
-            .analyze(&p, weak)
+        with_solver!(self.solver(), (s, t) => analyze_moves(s, t, &moves, weak, parallel))
+    }
+
+    /// compares every move of `moves` to the best ones, see `ReviewedMove`.
+    /// returns an empty array if `moves` is not a valid sequence.
+    /// solves every position strongly, which takes long early in the game without a book.
+    #[func]
+    fn review_game(&mut self, moves: PackedByteArray) -> Array<Gd<ReviewedMove>> {
+        let cols: Vec<usize> = moves.as_slice().iter().map(|&b| b as usize).collect();
+        match with_solver!(self.solver(), s => s.review_game(&cols)) {
+            Ok(reviews) => reviews
+                .iter()
+                .map(|review| Gd::from_object(ReviewedMove::new(review)))
+                .collect(),
+            Err(e) => {
+                godot_error!("invalid moves {moves}: {e}");
+                Array::new()
+            }
+        }
+    }
+
+    /// columns of the optimal continuation after `moves` until the game ends,
+    /// empty if `moves` is invalid or the game is over
+    #[func]
+    fn principal_variation(&mut self, moves: PackedByteArray) -> PackedByteArray {
+        with_solver!(self.solver(), s => position_for(s, &moves)
+            .map(|p| s.principal_variation(&p))
+            .unwrap_or_default()
             .into_iter()
-            .enumerate()
-            .map(|(i, s)| s.map(|s| Gd::from_object(AnalyzedMove::new(&p, i, -s))))
+            .map(|col| col as u8)
+            .collect())
+    }
+
+    /// a random position on the current board where the player to move wins in `win_in` moves
+    /// with a single winning column, the same `seed` giving the same puzzle.
+    /// returns a Dictionary with `moves` (a move list like the ones given to `solve`),
+    /// `move_string` (1-based digits), `solution` (the winning column) and `win_in`,
+    /// or an empty one if no puzzle was found.
+    #[func]
+    fn generate_puzzle(&mut self, win_in: i64, seed: i64) -> Dictionary {
+        let Ok(win_in) = usize::try_from(win_in) else {
+            godot_error!("invalid puzzle length {win_in}");
+            return Dictionary::new();
+        };
+        let puzzle = with_solver!(self.solver(), s => Puzzle::generate(s, win_in, seed as u64));
+        let Some(puzzle) = puzzle else {
+            return Dictionary::new();
+        };
+        let moves: PackedByteArray = puzzle
+            .moves
+            .chars()
+            .filter_map(|c| c.to_digit(10))
+            .map(|d| d as u8 - 1)
+            .collect();
+        let mut dict = Dictionary::new();
+        dict.set("moves", moves);
+        dict.set("move_string", GString::from(puzzle.moves.as_str()));
+        dict.set("solution", puzzle.solution as i64);
+        dict.set("win_in", puzzle.win_in as i64);
+        dict
+    }
+
+    /// like `solve`, for the current position of `game`, `INVALID_SCORE` if it is over.
+    /// switches to the board size of `game` if needed.
+    #[func]
+    fn solve_game(&mut self, game: Gd<C4Game>, #[opt(default = true)] weak: bool) -> i32 {
+        let game = game.bind();
+        if let Err(e) = with_game!(&game.game, g => g.position().check_not_over()) {
+            godot_error!("cannot solve game: {e}");
+            return Self::INVALID_SCORE;
+        }
+        let (width, height) = game.game.size();
+        self.set_board_size(width as u32, height as u32);
+        with_solver_and_game!(self.solver(), &game.game, (s, g) => s.solve(g.position(), weak))
+    }
+
+    /// like `analyze`, for the current position of `game`, empty if it is over.
+    /// switches to the board size of `game` if needed.
+    #[func]
+    fn analyze_game(
+        &mut self,
+        game: Gd<C4Game>,
+        #[opt(default = true)] weak: bool,
+        #[opt(default = false)] parallel: bool,
+    ) -> Array<Option<Gd<AnalyzedMove>>> {
+        let game = game.bind();
+        if let Err(e) = with_game!(&game.game, g => g.position().check_not_over()) {
+            godot_error!("cannot analyze game: {e}");
+            return Array::new();
+        }
+        let (width, height) = game.game.size();
+        self.set_board_size(width as u32, height as u32);
+        with_solver_and_game!(self.solver(), &game.game, (s, t, g) => {
+            MoveAnalysis::to_godot(&MoveAnalysis::analyze(s, t, g.position(), weak, parallel))
+        })
+    }
+}
+
+/// stops the `C4Solver` it was obtained from, usable while the solver is busy on another thread
+#[derive(GodotClass)]
+#[class(no_init)]
+struct C4StopToken {
+    stop: Arc<AtomicBool>,
+}
+
+#[godot_api]
+impl C4StopToken {
+    /// makes running and future searches return early until `reset`
+    #[func]
+    fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    #[func]
+    fn reset(&self) {
+        self.stop.store(false, Ordering::Relaxed);
+    }
+
+    #[func]
+    fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+/// a game in progress with undo/redo, meant to replace keeping the move list in GDScript.
+/// players are 0 (first) and 1 (second), rows are counted from the bottom.
+#[derive(GodotClass)]
+#[class(init, base = RefCounted)]
+struct C4Game {
+    game: AnyGame,
+    base: Base<RefCounted>,
+}
+
+#[godot_api]
+impl C4Game {
+    /// emitted after `play` or `redo` put a piece of `player` in (`col`, `row`)
+    #[signal]
+    fn move_played(col: i64, row: i64, player: i64);
+    /// emitted when a move ends the game, `winner` is -1 for a draw
+    #[signal]
+    fn game_over(winner: i64);
+
+    /// starts a new empty game on a `width`x`height` board, see `C4Solver.set_board_size`.
+    /// returns false and keeps the current game if the size is not supported.
+    #[func]
+    fn set_board_size(&mut self, width: u32, height: u32) -> bool {
+        match AnyGame::new(width as usize, height as usize) {
+            Some(game) => {
+                self.game = game;
+                true
+            }
+            None => false,
+        }
+    }
+
+    #[func]
+    fn get_board_size(&self) -> Vector2i {
+        let (width, height) = self.game.size();
+        Vector2i::new(width as i32, height as i32)
+    }
+
+    /// clears the board, keeping its size
+    #[func]
+    fn reset(&mut self) {
+        let (width, height) = self.game.size();
+        self.set_board_size(width as u32, height as u32);
+    }
+
+    /// returns false (and reports an error) if `col` cannot be played
+    #[func]
+    fn play(&mut self, col: i64) -> bool {
+        let result = with_game!(&mut self.game, g => g.play(col as usize));
+        match result {
+            Ok(()) => {
+                self.notify_move_played();
+                true
+            }
+            Err(e) => {
+                godot_error!("cannot play column {col}: {e}");
+                false
+            }
+        }
+    }
+
+    /// returns false if there is no move to undo
+    #[func]
+    fn undo(&mut self) -> bool {
+        with_game!(&mut self.game, g => g.undo()).is_some()
+    }
+
+    /// returns false if there is no undone move to replay
+    #[func]
+    fn redo(&mut self) -> bool {
+        let redone = with_game!(&mut self.game, g => g.redo()).is_some();
+        if redone {
+            self.notify_move_played();
+        }
+        redone
+    }
+
+    #[func]
+    fn can_play(&self, col: i64) -> bool {
+        col >= 0 && with_game!(&self.game, g => g.can_play(col as usize))
+    }
+
+    /// 0 or 1, the player to move next
+    #[func]
+    fn current_player(&self) -> i64 {
+        with_game!(&self.game, g => g.position().current_player() as i64)
+    }
+
+    /// player owning the cell, -1 if empty or out of the board
+    #[func]
+    fn cell(&self, col: i64, row: i64) -> i64 {
+        if col < 0 || row < 0 {
+            return -1;
+        }
+        with_game!(&self.game, g => g.position().cell(col as usize, row as usize))
+            .map_or(-1, |p| p as i64)
+    }
+
+    /// columns played so far, as accepted by `C4Solver.analyze`
+    #[func]
+    fn moves(&self) -> PackedByteArray {
+        with_game!(&self.game, g => g.moves().iter().map(|&col| col as u8).collect())
+    }
+
+    /// 0 or 1 if that player connected four, -1 otherwise
+    #[func]
+    fn winner(&self) -> i64 {
+        with_game!(&self.game, g => g.position().winner()).map_or(-1, |w| w as i64)
+    }
+
+    #[func]
+    fn is_draw(&self) -> bool {
+        with_game!(&self.game, g => g.position().is_draw())
+    }
+
+    #[func]
+    fn is_game_over(&self) -> bool {
+        with_game!(&self.game, g => g.position().is_game_over())
+    }
+
+    /// (col, row) of every cell in a completed line
+    #[func]
+    fn winning_cells(&self) -> Array<Vector2i> {
+        with_game!(&self.game, g => g.position().winning_cells())
+            .into_iter()
+            .map(|(col, row)| Vector2i::new(col as i32, row as i32))
             .collect()
     }
+
+    /// see `C4Solver.threats`
+    #[func]
+    fn threats(&self) -> Dictionary {
+        threats_to_godot(&with_game!(&self.game, g => g.position().threats()))
+    }
+
+    fn notify_move_played(&mut self) {
+        let (col, row, player, winner, over) = with_game!(&self.game, g => {
+            let p = g.position();
+            let col = *g.moves().last().expect("a move was just played");
+            (
+                col as i64,
+                p.column_height(col) as i64 - 1,
+                1 - p.current_player() as i64,
+                p.winner().map_or(-1, |w| w as i64),
+                p.is_game_over(),
+            )
+        });
+        self.signals().move_played().emit(col, row, player);
+        if over {
+            self.signals().game_over().emit(winner);
+        }
+    }
+}
+
+/// picks the move of an AI opponent from `C4Solver.analyze` results, see `set_difficulty`.
+/// depth-limited difficulties need `weak = false` analyses.
+#[derive(GodotClass)]
+#[class(init)]
+struct C4Player {
+    player: Player,
+    #[init(val = (7, 6))]
+    size: (usize, usize),
+}
+
+#[godot_api]
+impl C4Player {
+    #[constant]
+    const EASY: i64 = 0;
+    #[constant]
+    const MEDIUM: i64 = 1;
+    #[constant]
+    const HARD: i64 = 2;
+    #[constant]
+    const PERFECT: i64 = 3;
+
+    /// see `C4Solver.set_board_size`
+    #[func]
+    fn set_board_size(&mut self, width: u32, height: u32) -> bool {
+        let size = (width as usize, height as usize);
+        if AnySolver::supports(size.0, size.1) {
+            self.size = size;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// one of `EASY`, `MEDIUM`, `HARD` or `PERFECT`, returns false for other values
+    #[func]
+    fn set_difficulty(&mut self, difficulty: i64) -> bool {
+        self.player.strength = match difficulty {
+            Self::EASY => Strength::EASY,
+            Self::MEDIUM => Strength::MEDIUM,
+            Self::HARD => Strength::HARD,
+            Self::PERFECT => Strength::PERFECT,
+            _ => return false,
+        };
+        true
+    }
+
+    /// custom difficulty, `max_depth` < 0 for no depth limit
+    #[func]
+    fn set_strength(
+        &mut self,
+        temperature: f64,
+        blunder_chance: f64,
+        max_depth: i64,
+        never_miss_tactics: bool,
+    ) {
+        self.player.strength = Strength {
+            temperature,
+            blunder_chance,
+            max_depth: (max_depth >= 0).then_some(max_depth as usize),
+            never_miss_tactics,
+        };
+    }
+
+    /// makes the following choices reproducible
+    #[func]
+    fn set_seed(&mut self, seed: i64) {
+        self.player.seed(seed as u64);
+    }
+
+    /// column to play after `moves`, -1 if there is none or `moves` is invalid
+    #[func]
+    fn choose_move(
+        &mut self,
+        moves: PackedByteArray,
+        analyses: Array<Option<Gd<AnalyzedMove>>>,
+    ) -> i64 {
+        with_board_size!(self.size, (W, H, B) => {
+            choose_move::<W, H, B>(&mut self.player, &moves, &analyses)
+        })
+    }
+}
+
+/// `Player::choose` on a `W`x`H` board
+fn choose_move<const W: usize, const H: usize, B: Bitboard>(
+    player: &mut Player,
+    moves: &PackedByteArray,
+    analyses: &Array<Option<Gd<AnalyzedMove>>>,
+) -> i64 {
+    let Some(position) = position_from_moves::<W, H, B>(moves) else {
+        return -1;
+    };
+    let mut scores = vec![None; W];
+    for analysis in analyses.iter_shared().flatten() {
+        let analysis = analysis.bind();
+        if let Some(score) = scores.get_mut(analysis.col as usize) {
+            *score = Some(analysis.score);
+        }
+    }
+    player
+        .choose(&position, &scores)
+        .map_or(-1, |col| col as i64)
 }
 
 struct MyExtension;